@@ -1,10 +1,17 @@
 use crate::DlmError;
 use crate::DlmError::CliArgumentError;
+use crate::conflict::ConflictPolicy;
+use crate::html_links::LinkFilter;
+use crate::json_input;
+use crate::template::OutputTemplate;
+use crate::url_pattern::UrlPattern;
 use crate::user_agents::UserAgent;
 use crate::user_agents::UserAgent::{CustomUserAgent, RandomUserAgent};
 use crate::user_agents::print_user_agents;
+use clap::parser::ValueSource;
 use clap::{Arg, Command};
 use clap::{crate_authors, crate_description, crate_name, crate_version};
+use regex::Regex;
 use std::path::{Path, PathBuf};
 
 fn command() -> Command {
@@ -32,14 +39,138 @@ fn command() -> Command {
                 .value_parser(clap::value_parser!(u32).range(1..))
                 .default_value("2"),
         )
+        .arg(
+            Arg::new("maxConcurrentDownloadsPerDomain")
+                .help("Maximum number of concurrent downloads per host")
+                .long("max-concurrent-per-domain")
+                .num_args(1)
+                // reject 0: downloads for any host would wait forever.
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .required(false),
+        )
+        .arg(
+            Arg::new("autopilot")
+                .help("Adapt the number of concurrent downloads to throughput and errors")
+                .long("autopilot")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("segments")
+                .help("Number of parallel connections per file (requires server range support)")
+                .long("segments")
+                .num_args(1)
+                // reject 0: a file cannot be split into zero byte ranges.
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("maxBandwidth")
+                .help("Maximum total download rate shared by all downloads (e.g. 500K, 2M)")
+                .long("max-bandwidth")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("maxBandwidthPerDownload")
+                .help("Maximum download rate of each download (e.g. 500K, 2M)")
+                .long("max-bandwidth-per-download")
+                .num_args(1)
+                .required(false),
+        )
         .arg(
             Arg::new("inputFile")
-                .help("Input file with links")
+                .help("Input file with links ('-' for stdin)")
                 .long("input-file")
                 .short('i')
                 .num_args(1)
                 .conflicts_with("url"),
         )
+        .arg(
+            Arg::new("follow")
+                .help("Keep reading the input file for appended links")
+                .long("follow")
+                .requires("inputFile")
+                .conflicts_with("interleaveDomains")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("metalink")
+                .help("Metalink (.meta4) file listing the files to download")
+                .long("metalink")
+                .num_args(1)
+                .conflicts_with_all(["url", "inputFile"]),
+        )
+        .arg(
+            Arg::new("fromHtml")
+                .help("Web page or HTML file to download the links of")
+                .long("from-html")
+                .num_args(1)
+                .conflicts_with_all(["url", "inputFile", "metalink"]),
+        )
+        .arg(
+            Arg::new("recursive")
+                .help("Crawl the subdirectories of the directory listing given to --from-html")
+                .long("recursive")
+                .requires("fromHtml")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("maxDepth")
+                .help("Maximum depth of the subdirectories crawled by --recursive")
+                .long("max-depth")
+                .num_args(1)
+                .default_value("5")
+                .value_parser(clap::value_parser!(u32))
+                .requires("recursive"),
+        )
+        .arg(
+            Arg::new("linkRegex")
+                .help("Only download the links matching this regular expression")
+                .long("link-regex")
+                .num_args(1)
+                .requires("fromHtml"),
+        )
+        .arg(
+            Arg::new("linkExtensions")
+                .help("Only download the links with one of these extensions (e.g. 'iso,zip')")
+                .long("link-ext")
+                .num_args(1)
+                .requires("fromHtml"),
+        )
+        .arg(
+            Arg::new("sameHost")
+                .help("Only download the links on the host of the page")
+                .long("same-host")
+                .requires("fromHtml")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("interleaveDomains")
+                .help("Reorder the input file round-robin by host")
+                .long("interleave-domains")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("glob")
+                .help("Expand URL patterns such as 'part[001-250].bin' or '{eu,us}'")
+                .long("glob")
+                .conflicts_with_all(["metalink", "fromHtml"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("checksums")
+                .help("Checksum manifest file or URL to verify downloads against")
+                .long("checksums")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("output")
+                .help("Name of the downloaded file (single URL only)")
+                .long("output")
+                .short('O')
+                .num_args(1),
+        )
         .arg(
             Arg::new("outputDir")
                 .help("Output directory for downloads")
@@ -48,6 +179,39 @@ fn command() -> Command {
                 .default_value(".")
                 .num_args(1),
         )
+        .arg(
+            Arg::new("keepPaths")
+                .help("Save files under host/path/ as in their URL")
+                .long("keep-paths")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("outputTemplate")
+                .help("Destination of each file, e.g. '{host}/{yyyy}-{mm}/{filename}'")
+                .long("output-template")
+                .num_args(1)
+                .conflicts_with_all(["output", "keepPaths"]),
+        )
+        .arg(
+            Arg::new("onConflict")
+                .help("What to do when the destination file already exists")
+                .long("on-conflict")
+                .num_args(1)
+                .value_parser(ConflictPolicy::NAMES)
+                .default_value("skip"),
+        )
+        .arg(
+            Arg::new("update")
+                .help("Download existing files again if the remote file changed")
+                .long("update")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("remoteTime")
+                .help("Set the modification time of files from the server")
+                .long("remote-time")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("userAgent")
                 .help("User-Agent header to use")
@@ -135,12 +299,43 @@ fn command() -> Command {
 
 pub enum Input {
     File(String),
-    Url(String),
+    Stdin,
+    Url {
+        url: String,
+        /// Name to save the file under, from `--output`.
+        output: Option<String>,
+    },
+    Metalink(String),
+    /// Input file holding a JSON array or JSON Lines.
+    Json(String),
+    /// Web page or HTML file whose links are downloaded.
+    Html(String),
 }
 
 pub struct Arguments {
     pub input: Input,
+    pub interleave_domains: bool,
+    /// Tail the input file for appended links instead of stopping at its end.
+    pub follow: bool,
+    /// Expand the URL patterns of the inputs.
+    pub glob: bool,
+    /// Links of `Input::Html` to download.
+    pub link_filter: LinkFilter,
+    /// Depth of the subdirectories crawled from `Input::Html`, `None` to
+    /// only download the links of the page.
+    pub crawl_depth: Option<u32>,
+    pub checksums: Option<String>,
+    pub keep_paths: bool,
+    pub output_template: Option<OutputTemplate>,
+    pub on_conflict: ConflictPolicy,
+    pub update: bool,
+    pub remote_time: bool,
     pub max_concurrent_downloads: u32,
+    pub max_concurrent_downloads_per_domain: Option<u32>,
+    pub autopilot: bool,
+    pub segments: u32,
+    pub max_bandwidth: Option<u64>,
+    pub max_bandwidth_per_download: Option<u64>,
     pub output_dir: PathBuf,
     pub user_agent: Option<UserAgent>,
     pub proxy: Option<String>,
@@ -153,7 +348,7 @@ pub struct Arguments {
 }
 
 /// Parse a single `Name: Value` header argument.
-fn parse_header(raw: &str) -> Result<(String, String), DlmError> {
+pub fn parse_header(raw: &str) -> Result<(String, String), DlmError> {
     let (name, value) = raw.split_once(':').ok_or_else(|| CliArgumentError {
         message: format!("invalid header '{raw}', expected 'Name: Value'"),
     })?;
@@ -167,6 +362,30 @@ fn parse_header(raw: &str) -> Result<(String, String), DlmError> {
     Ok((name.to_string(), value.to_string()))
 }
 
+/// Parse a bandwidth argument in bytes per second, with an optional binary
+/// `K`, `M` or `G` suffix (`500K` = 500 KiB/s). A trailing `B`/`iB` is accepted.
+fn parse_bandwidth(arg_name: &str, raw: &str) -> Result<u64, DlmError> {
+    let invalid = || CliArgumentError {
+        message: format!("invalid '--{arg_name}' value '{raw}', expected e.g. '500K' or '2M'"),
+    };
+    let upper = raw.trim().to_ascii_uppercase();
+    let value = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+    let (digits, multiplier) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    let amount: u64 = digits.trim().parse().map_err(|_| invalid())?;
+    match amount.checked_mul(multiplier) {
+        Some(0) | None => Err(invalid()),
+        Some(bytes) => Ok(bytes),
+    }
+}
+
 /// Parse a `user:password` basic-auth argument. The password may contain colons.
 fn parse_basic_auth(raw: &str) -> Result<(String, String), DlmError> {
     let (user, pass) = raw.split_once(':').ok_or_else(|| CliArgumentError {
@@ -195,28 +414,152 @@ pub fn get_args() -> Result<Arguments, DlmError> {
         .get_one("maxConcurrentDownloads")
         .expect("impossible");
 
+    let max_concurrent_downloads_per_domain: Option<u32> =
+        matches.get_one("maxConcurrentDownloadsPerDomain").copied();
+
+    let autopilot = matches.get_flag("autopilot");
+
+    let segments: u32 = *matches.get_one("segments").expect("impossible");
+
+    let max_bandwidth = matches
+        .get_one::<String>("maxBandwidth")
+        .map(|s| parse_bandwidth("max-bandwidth", s))
+        .transpose()?;
+
+    let max_bandwidth_per_download = matches
+        .get_one::<String>("maxBandwidthPerDownload")
+        .map(|s| parse_bandwidth("max-bandwidth-per-download", s))
+        .transpose()?;
+
     let url = matches.get_one::<String>("url");
     let input_file = matches.get_one::<String>("inputFile");
+    let metalink = matches.get_one::<String>("metalink");
+    let from_html = matches.get_one::<String>("fromHtml");
+    let output = matches.get_one::<String>("output");
+    if output.is_some() && url.is_none() {
+        return Err(CliArgumentError {
+            message: "'--output' requires a URL".to_string(),
+        });
+    }
 
     // Process mutually exclusive inputs
-    let input = match (url, input_file) {
-        (None, None) | (Some(_), Some(_)) => Err(CliArgumentError {
-            message: "provide either a URL or --input-file".to_string(),
+    let follow = matches.get_flag("follow");
+    let input = match (url, input_file, metalink, from_html) {
+        (Some(url), None, None, None) => Ok(Input::Url {
+            url: url.trim().to_string(),
+            output: output.cloned(),
         }),
-        (Some(url), None) => Ok(Input::Url(url.trim().to_string())),
-        (None, Some(file)) => {
+        (None, Some(file), None, None) => {
             let input_file = file.trim();
-            if Path::new(input_file).is_file() {
-                Ok(Input::File(input_file.to_string()))
+            if input_file == "-" {
+                Ok(Input::Stdin)
+            } else if Path::new(input_file).is_file() {
+                // a followed file is read line by line, as JSON Lines if need be
+                if !follow && json_input::is_json_file(Path::new(input_file))? {
+                    Ok(Input::Json(input_file.to_string()))
+                } else {
+                    Ok(Input::File(input_file.to_string()))
+                }
             } else {
                 Err(CliArgumentError {
                     message: "'inputFile' does not exist".to_string(),
                 })
             }
         }
+        (None, None, Some(file), None) => {
+            let metalink = file.trim();
+            if Path::new(metalink).is_file() {
+                Ok(Input::Metalink(metalink.to_string()))
+            } else {
+                Err(CliArgumentError {
+                    message: "'metalink' does not exist".to_string(),
+                })
+            }
+        }
+        (None, None, None, Some(source)) => {
+            let source = source.trim();
+            let is_url = source.starts_with("http://") || source.starts_with("https://");
+            if is_url || Path::new(source).is_file() {
+                Ok(Input::Html(source.to_string()))
+            } else {
+                Err(CliArgumentError {
+                    message: "'fromHtml' does not exist".to_string(),
+                })
+            }
+        }
+        _ => Err(CliArgumentError {
+            message: "provide either a URL, --input-file, --metalink or --from-html".to_string(),
+        }),
     };
     let input = input?;
 
+    let interleave_domains = matches.get_flag("interleaveDomains");
+    if interleave_domains
+        && !matches!(
+            input,
+            Input::File(_) | Input::Stdin | Input::Json(_) | Input::Html(_)
+        )
+    {
+        return Err(CliArgumentError {
+            message: "'--interleave-domains' requires '--input-file'".to_string(),
+        });
+    }
+
+    if follow && matches!(input, Input::Stdin) {
+        return Err(CliArgumentError {
+            message: "'--follow' cannot be used with '--input-file -'".to_string(),
+        });
+    }
+
+    let glob = matches.get_flag("glob");
+    if glob
+        && let Input::Url { url, output } = &input
+        && UrlPattern::parse(url)?.len() > 1
+        && output.is_some()
+    {
+        return Err(CliArgumentError {
+            message: "'--output' cannot name the several files of a URL pattern".to_string(),
+        });
+    }
+
+    let link_filter = LinkFilter {
+        regex: matches
+            .get_one::<String>("linkRegex")
+            .map(|s| {
+                Regex::new(s).map_err(|e| CliArgumentError {
+                    message: format!("invalid '--link-regex' value - {e}"),
+                })
+            })
+            .transpose()?,
+        extensions: matches
+            .get_one::<String>("linkExtensions")
+            .map(|s| {
+                s.split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|extension| !extension.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        same_host: matches.get_flag("sameHost"),
+    };
+
+    let crawl_depth = matches
+        .get_flag("recursive")
+        .then(|| *matches.get_one::<u32>("maxDepth").expect("impossible"));
+    if crawl_depth.is_some()
+        && let Input::Html(source) = &input
+        && !source.starts_with("http://")
+        && !source.starts_with("https://")
+    {
+        return Err(CliArgumentError {
+            message: "'--recursive' requires a URL for '--from-html'".to_string(),
+        });
+    }
+
+    let checksums = matches
+        .get_one::<String>("checksums")
+        .map(|s| s.trim().to_string());
+
     let output_dir = PathBuf::from(
         matches
             .get_one::<String>("outputDir")
@@ -229,6 +572,32 @@ pub fn get_args() -> Result<Arguments, DlmError> {
         });
     }
 
+    let keep_paths = matches.get_flag("keepPaths");
+    let output_template = matches
+        .get_one::<String>("outputTemplate")
+        .map(|template| OutputTemplate::parse(template))
+        .transpose()?;
+    let update = matches.get_flag("update");
+
+    // safe match because of default value and possible values
+    let on_conflict = matches
+        .get_one::<String>("onConflict")
+        .and_then(|name| ConflictPolicy::from_name(name))
+        .expect("impossible");
+    // an update replaces files that changed, keeping them defeats its purpose
+    let on_conflict = match on_conflict {
+        ConflictPolicy::Skip if update => {
+            if matches.value_source("onConflict") != Some(ValueSource::DefaultValue) {
+                return Err(CliArgumentError {
+                    message: "'--update' cannot be used with '--on-conflict skip'".to_string(),
+                });
+            }
+            ConflictPolicy::Overwrite
+        }
+        policy => policy,
+    };
+    let remote_time = matches.get_flag("remoteTime");
+
     let user_agent: Option<UserAgent> = match matches.get_one::<String>("userAgent") {
         Some(user_agent) => Some(CustomUserAgent(user_agent.clone())),
         None if matches.get_flag("randomUserAgent") => Some(RandomUserAgent),
@@ -271,7 +640,23 @@ pub fn get_args() -> Result<Arguments, DlmError> {
 
     Ok(Arguments {
         input,
+        interleave_domains,
+        follow,
+        glob,
+        link_filter,
+        crawl_depth,
+        checksums,
+        keep_paths,
+        output_template,
+        on_conflict,
+        update,
+        remote_time,
         max_concurrent_downloads,
+        max_concurrent_downloads_per_domain,
+        autopilot,
+        segments,
+        max_bandwidth,
+        max_bandwidth_per_download,
         output_dir,
         user_agent,
         proxy,
@@ -318,11 +703,59 @@ mod args_tests {
     -m, --max-concurrent <maxConcurrentDownloads>
     Maximum number of concurrent downloads
     [default: 2]
+    --max-concurrent-per-domain <maxConcurrentDownloadsPerDomain>
+    Maximum number of concurrent downloads per host
+    --autopilot
+    Adapt the number of concurrent downloads to throughput and errors
+    --segments <segments>
+    Number of parallel connections per file (requires server range support)
+    [default: 1]
+    --max-bandwidth <maxBandwidth>
+    Maximum total download rate shared by all downloads (e.g. 500K, 2M)
+    --max-bandwidth-per-download <maxBandwidthPerDownload>
+    Maximum download rate of each download (e.g. 500K, 2M)
     -i, --input-file <inputFile>
-    Input file with links
+    Input file with links ('-' for stdin)
+    --follow
+    Keep reading the input file for appended links
+    --metalink <metalink>
+    Metalink (.meta4) file listing the files to download
+    --from-html <fromHtml>
+    Web page or HTML file to download the links of
+    --recursive
+    Crawl the subdirectories of the directory listing given to --from-html
+    --max-depth <maxDepth>
+    Maximum depth of the subdirectories crawled by --recursive
+    [default: 5]
+    --link-regex <linkRegex>
+    Only download the links matching this regular expression
+    --link-ext <linkExtensions>
+    Only download the links with one of these extensions (e.g. 'iso,zip')
+    --same-host
+    Only download the links on the host of the page
+    --interleave-domains
+    Reorder the input file round-robin by host
+    --glob
+    Expand URL patterns such as 'part[001-250].bin' or '{eu,us}'
+    --checksums <checksums>
+    Checksum manifest file or URL to verify downloads against
+    -O, --output <output>
+    Name of the downloaded file (single URL only)
     -o, --output-dir <outputDir>
     Output directory for downloads
     [default: .]
+    --keep-paths
+    Save files under host/path/ as in their URL
+    --output-template <outputTemplate>
+    Destination of each file, e.g. '{host}/{yyyy}-{mm}/{filename}'
+    --on-conflict <onConflict>
+    What to do when the destination file already exists
+    [default: skip]
+    [possible values: skip, overwrite, rename, backup]
+    --update
+    Download existing files again if the remote file changed
+    --remote-time
+    Set the modification time of files from the server
     -u, --user-agent <userAgent>
     User-Agent header to use
     --random-user-agent
@@ -387,6 +820,42 @@ mod args_tests {
         assert!(super::parse_header(": value").is_err());
     }
 
+    #[test]
+    fn parse_bandwidth_plain_bytes() {
+        assert_eq!(
+            super::parse_bandwidth("max-bandwidth", "2048").unwrap(),
+            2048
+        );
+    }
+
+    #[test]
+    fn parse_bandwidth_binary_suffixes() {
+        assert_eq!(
+            super::parse_bandwidth("max-bandwidth", "500K").unwrap(),
+            512_000
+        );
+        assert_eq!(
+            super::parse_bandwidth("max-bandwidth", "2m").unwrap(),
+            2_097_152
+        );
+        assert_eq!(
+            super::parse_bandwidth("max-bandwidth", "1GiB").unwrap(),
+            1_073_741_824
+        );
+        assert_eq!(
+            super::parse_bandwidth("max-bandwidth", "3MB").unwrap(),
+            3_145_728
+        );
+    }
+
+    #[test]
+    fn parse_bandwidth_rejects_zero_and_garbage() {
+        assert!(super::parse_bandwidth("max-bandwidth", "0").is_err());
+        assert!(super::parse_bandwidth("max-bandwidth", "fast").is_err());
+        assert!(super::parse_bandwidth("max-bandwidth", "K").is_err());
+        assert!(super::parse_bandwidth("max-bandwidth", "-5M").is_err());
+    }
+
     #[test]
     fn parse_basic_auth_ok() {
         let (u, p) = super::parse_basic_auth("alice:s3cret").unwrap();
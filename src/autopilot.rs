@@ -0,0 +1,153 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of concurrent downloads the autopilot starts with.
+const INITIAL_TARGET: u32 = 1;
+
+/// Share of the best aggregate throughput seen so far below which a new
+/// sample is considered a slowdown.
+const SLOWDOWN_RATIO: f64 = 0.9;
+
+/// AIMD controller picking how many downloads run at once.
+///
+/// - every completed download yields a throughput sample (bytes over the
+///   wall time of the download, so latency and retries count against it);
+///   the aggregate throughput is estimated as that rate times the current
+///   target.
+/// - additive increase: one more slot while the aggregate keeps up with the
+///   best seen so far.
+/// - additive decrease: one slot less once adding slots stops paying off.
+/// - multiplicative decrease: half the slots on every retryable error, the
+///   usual sign of an overloaded server (429, 503, timeouts...).
+pub struct Autopilot {
+    max: u32,
+    // `std::sync::Mutex` is fine here — the lock is never held across an `.await`
+    state: Mutex<State>,
+}
+
+struct State {
+    target: u32,
+    best_rate: f64,
+}
+
+impl Autopilot {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            state: Mutex::new(State {
+                target: INITIAL_TARGET.min(max),
+                best_rate: 0.0,
+            }),
+        }
+    }
+
+    pub fn target(&self) -> u32 {
+        self.lock().target
+    }
+
+    /// Feed a completed download of `size` bytes, `resumed_from` of which
+    /// were already on disk and not transferred. Returns the new target when
+    /// it changed.
+    pub fn on_success(&self, size: u64, resumed_from: u64, elapsed: Duration) -> Option<u32> {
+        let bytes = size.saturating_sub(resumed_from);
+        let secs = elapsed.as_secs_f64();
+        // skipped downloads transfer nothing and say nothing about the link
+        if bytes == 0 || secs <= 0.0 {
+            return None;
+        }
+        let mut state = self.lock();
+        let aggregate_rate = bytes as f64 / secs * f64::from(state.target);
+        let previous = state.target;
+        if aggregate_rate >= state.best_rate * SLOWDOWN_RATIO {
+            state.best_rate = state.best_rate.max(aggregate_rate);
+            state.target = (state.target + 1).min(self.max);
+        } else {
+            state.target = (state.target - 1).max(1);
+        }
+        (state.target != previous).then_some(state.target)
+    }
+
+    /// Feed a retryable error. Returns the new target when it changed.
+    pub fn on_retryable_error(&self) -> Option<u32> {
+        let mut state = self.lock();
+        let previous = state.target;
+        state.target = (state.target / 2).max(1);
+        (state.target != previous).then_some(state.target)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("autopilot lock should not be poisoned")
+    }
+}
+
+#[cfg(test)]
+mod autopilot_tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn starts_low() {
+        assert_eq!(Autopilot::new(8).target(), 1);
+    }
+
+    #[test]
+    fn never_exceeds_max() {
+        let autopilot = Autopilot::new(3);
+        for _ in 0..10 {
+            autopilot.on_success(1000, 0, SEC);
+        }
+        assert_eq!(autopilot.target(), 3);
+    }
+
+    #[test]
+    fn grows_while_throughput_scales() {
+        let autopilot = Autopilot::new(8);
+        // each download keeps the same speed → aggregate grows with the target
+        assert_eq!(autopilot.on_success(1000, 0, SEC), Some(2));
+        assert_eq!(autopilot.on_success(1000, 0, SEC), Some(3));
+        assert_eq!(autopilot.on_success(1000, 0, SEC), Some(4));
+    }
+
+    #[test]
+    fn backs_off_when_throughput_stops_scaling() {
+        let autopilot = Autopilot::new(8);
+        autopilot.on_success(1000, 0, SEC); // 1 → 2, best 1000
+        autopilot.on_success(1000, 0, SEC); // 2 → 3, best 2000
+        // 3 downloads now share the link: 300 B/s each → 900 B/s aggregate
+        assert_eq!(autopilot.on_success(300, 0, SEC), Some(2));
+    }
+
+    #[test]
+    fn resumed_bytes_are_not_throughput() {
+        let autopilot = Autopilot::new(8);
+        autopilot.on_success(1000, 0, SEC); // 1 → 2, best 1000
+        // 10_000 bytes on disk but only 100 transferred: a slow resume
+        assert_eq!(autopilot.on_success(10_000, 9_900, SEC), Some(1));
+        // a `.part` that was already complete says nothing about the link
+        assert_eq!(autopilot.on_success(10_000, 10_000, SEC), None);
+    }
+
+    #[test]
+    fn retryable_error_halves_the_target() {
+        let autopilot = Autopilot::new(16);
+        for _ in 0..7 {
+            autopilot.on_success(1000, 0, SEC);
+        }
+        assert_eq!(autopilot.target(), 8);
+        assert_eq!(autopilot.on_retryable_error(), Some(4));
+        assert_eq!(autopilot.on_retryable_error(), Some(2));
+        assert_eq!(autopilot.on_retryable_error(), Some(1));
+        assert_eq!(autopilot.on_retryable_error(), None);
+    }
+
+    #[test]
+    fn empty_samples_are_ignored() {
+        let autopilot = Autopilot::new(8);
+        assert_eq!(autopilot.on_success(0, 0, SEC), None);
+        assert_eq!(autopilot.on_success(1000, 0, Duration::ZERO), None);
+        assert_eq!(autopilot.target(), 1);
+    }
+}
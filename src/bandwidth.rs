@@ -0,0 +1,87 @@
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant, sleep};
+
+/// Token bucket capping a byte rate. A single limiter can be shared by any
+/// number of concurrent downloads, which then split the rate between them.
+///
+/// The bucket holds at most one second worth of tokens. Callers take tokens
+/// after receiving a chunk and may drive the balance negative — chunk sizes
+/// are picked by the server — then sleep until the debt is paid back, which
+/// keeps the long-run rate on target without ever splitting a chunk.
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec as f64;
+        Self {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Account for `bytes` just received, waiting as long as needed to stay
+    /// under the configured rate.
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last_refill).as_secs_f64() * self.bytes_per_sec;
+            bucket.tokens = (bucket.tokens + refill).min(self.bytes_per_sec);
+            bucket.last_refill = now;
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec)
+            } else {
+                Duration::ZERO
+            }
+        };
+        // the lock is released before sleeping so other downloads can queue
+        // their own debt behind this one
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limiter_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn burst_within_capacity_does_not_wait() {
+        let limiter = RateLimiter::new(10_000);
+        let start = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn debt_is_paid_back_at_the_configured_rate() {
+        let limiter = RateLimiter::new(10_000);
+        let start = Instant::now();
+        limiter.acquire(10_000).await;
+        limiter.acquire(5_000).await;
+        // 5_000 bytes over capacity at 10_000 B/s → ~500ms
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn shared_limiter_splits_the_rate() {
+        let limiter = RateLimiter::new(10_000);
+        let start = Instant::now();
+        // 2 tasks × 10_000 bytes, 10_000 of which fit the initial burst
+        tokio::join!(limiter.acquire(10_000), limiter.acquire(10_000));
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+}
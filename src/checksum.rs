@@ -0,0 +1,235 @@
+use crate::dlm_error::DlmError;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use tokio::fs as tfs;
+use tokio::io::AsyncReadExt;
+
+/// Supported digests, from the weakest to the strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Accepts both the plain (`sha256`) and the hyphenated (`sha-256`)
+    /// spelling, case-insensitively.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Some(Self::Sha256),
+            "sha384" => Some(Self::Sha384),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Guess the algorithm from the length of a hex digest, for formats that
+    /// don't name it.
+    pub fn from_hex_len(len: usize) -> Option<Self> {
+        [Self::Sha256, Self::Sha384, Self::Sha512]
+            .into_iter()
+            .find(|algorithm| algorithm.hex_len() == len)
+    }
+
+    /// Length of the digest in hex characters.
+    const fn hex_len(self) -> usize {
+        match self {
+            Self::Sha256 => 64,
+            Self::Sha384 => 96,
+            Self::Sha512 => 128,
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Expected digest of a download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex digest.
+    pub expected: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: HashAlgorithm, hex: &str) -> Result<Self, DlmError> {
+        let hex = hex.trim();
+        if hex.len() != algorithm.hex_len() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(DlmError::other(format!(
+                "invalid {algorithm} digest '{hex}', expected {} hex characters",
+                algorithm.hex_len()
+            )));
+        }
+        Ok(Self {
+            algorithm,
+            expected: hex.to_ascii_lowercase(),
+        })
+    }
+
+    /// Parse an `algorithm=hex` pair such as `sha256=9f86d0...`.
+    pub fn parse(raw: &str) -> Result<Self, DlmError> {
+        let (name, hex) = raw
+            .split_once('=')
+            .ok_or_else(|| DlmError::other(format!("invalid checksum '{raw}'")))?;
+        let algorithm = HashAlgorithm::from_name(name)
+            .ok_or_else(|| DlmError::other(format!("unsupported checksum algorithm '{name}'")))?;
+        Self::new(algorithm, hex)
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        match self.algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha384 => Hasher::Sha384(Sha384::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    /// Hash the whole content of `path` and compare it with the expected digest.
+    pub async fn verify_file(&self, path: &Path) -> Result<(), DlmError> {
+        let mut hasher = self.hasher();
+        hasher.update_from_file(path).await?;
+        self.verify(hasher)
+    }
+
+    /// Compare the digest computed by `hasher` with the expected one.
+    pub fn verify(&self, hasher: Hasher) -> Result<(), DlmError> {
+        let actual = hasher.finalize_hex();
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(DlmError::ChecksumMismatch {
+                algorithm: self.algorithm.to_string(),
+                expected: self.expected.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+/// Incremental digest fed chunk by chunk while a body is streamed.
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Sha384(h) => h.update(data),
+            Self::Sha512(h) => h.update(data),
+        }
+    }
+
+    /// Feed the whole content of `path`, e.g. the prefix held by a `.part`
+    /// before resuming it.
+    pub async fn update_from_file(&mut self, path: &Path) -> Result<(), DlmError> {
+        let mut file = tfs::File::open(path).await?;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buffer[..read]);
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        let digest = match self {
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Sha384(h) => h.finalize().to_vec(),
+            Self::Sha512(h) => h.finalize().to_vec(),
+        };
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // sha256("abc")
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn parse_plain_and_hyphenated_names() {
+        let plain = Checksum::parse(&format!("sha256={ABC_SHA256}")).unwrap();
+        let hyphen = Checksum::parse(&format!("SHA-256={ABC_SHA256}")).unwrap();
+        assert_eq!(plain, hyphen);
+        assert_eq!(plain.algorithm, HashAlgorithm::Sha256);
+    }
+
+    #[test]
+    fn parse_lowercases_digest() {
+        let checksum = Checksum::parse(&format!("sha256={}", ABC_SHA256.to_uppercase())).unwrap();
+        assert_eq!(checksum.expected, ABC_SHA256);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_algorithm() {
+        assert!(Checksum::parse("md5=900150983cd24fb0d6963f7d28e17f72").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_wrong_length_or_non_hex() {
+        assert!(Checksum::parse("sha256=abc").is_err());
+        assert!(Checksum::parse(&format!("sha512={ABC_SHA256}")).is_err());
+        assert!(Checksum::parse(&format!("sha256={}", "z".repeat(64))).is_err());
+        assert!(Checksum::parse("sha256").is_err());
+    }
+
+    #[test]
+    fn verify_streamed_chunks() {
+        let checksum = Checksum::parse(&format!("sha256={ABC_SHA256}")).unwrap();
+        let mut hasher = checksum.hasher();
+        hasher.update(b"a");
+        hasher.update(b"bc");
+        assert!(checksum.verify(hasher).is_ok());
+    }
+
+    #[test]
+    fn verify_reports_mismatch() {
+        let checksum = Checksum::parse(&format!("sha256={ABC_SHA256}")).unwrap();
+        let mut hasher = checksum.hasher();
+        hasher.update(b"abd");
+        match checksum.verify(hasher) {
+            Err(DlmError::ChecksumMismatch {
+                algorithm,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(algorithm, "sha256");
+                assert_eq!(expected, ABC_SHA256);
+                assert_ne!(actual, ABC_SHA256);
+            }
+            other => panic!("expected mismatch, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn resumed_prefix_and_stream_hash_like_a_single_pass() {
+        let dir = tempdir().unwrap();
+        let part = dir.path().join("file.part");
+        std::fs::write(&part, b"ab").unwrap();
+
+        let checksum = Checksum::parse(&format!("sha256={ABC_SHA256}")).unwrap();
+        let mut hasher = checksum.hasher();
+        hasher.update_from_file(&part).await.unwrap();
+        hasher.update(b"c");
+        assert!(checksum.verify(hasher).is_ok());
+    }
+}
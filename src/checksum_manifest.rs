@@ -0,0 +1,144 @@
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::client::{ClientConfig, fetch_text, make_client};
+use crate::dlm_error::DlmError;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs as tfs;
+
+/// Expected checksums by filename, loaded from a `SHA256SUMS`-style file.
+pub struct ChecksumManifest {
+    checksums: HashMap<String, Checksum>,
+}
+
+impl ChecksumManifest {
+    /// Load a manifest from a local path or an `http(s)://` URL.
+    pub async fn load(source: &str, client_config: &ClientConfig<'_>) -> Result<Self, DlmError> {
+        let content = if source.starts_with("http://") || source.starts_with("https://") {
+            let client = make_client(client_config, true)?;
+            fetch_text(&client, source).await?
+        } else {
+            tfs::read_to_string(source).await?
+        };
+        Self::parse(&content)
+    }
+
+    /// Parse a manifest in either of the formats produced by the usual tools:
+    /// - GNU coreutils: `<hex>  <name>` (text mode) or `<hex> *<name>` (binary mode)
+    /// - BSD: `SHA256 (<name>) = <hex>`
+    ///
+    /// Entries are keyed by file name only, `./dist/file.iso` matches a
+    /// download saved as `file.iso`. Lines using an unsupported algorithm
+    /// (e.g. MD5) are skipped.
+    pub fn parse(content: &str) -> Result<Self, DlmError> {
+        let checksums: HashMap<String, Checksum> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| parse_bsd_line(line).or_else(|| parse_gnu_line(line)))
+            .collect();
+        if checksums.is_empty() {
+            return Err(DlmError::CliArgumentError {
+                message: "no supported checksum found in the checksums manifest".to_string(),
+            });
+        }
+        Ok(Self { checksums })
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&Checksum> {
+        self.checksums.get(filename)
+    }
+
+    pub fn len(&self) -> usize {
+        self.checksums.len()
+    }
+}
+
+/// `SHA256 (name) = hex`
+fn parse_bsd_line(line: &str) -> Option<(String, Checksum)> {
+    let (left, hex) = line.rsplit_once(" = ")?;
+    let (name, path) = left.split_once(" (")?;
+    let path = path.strip_suffix(')')?;
+    let checksum = Checksum::new(HashAlgorithm::from_name(name)?, hex).ok()?;
+    Some((base_name(path)?, checksum))
+}
+
+/// `hex  name` or `hex *name`
+fn parse_gnu_line(line: &str) -> Option<(String, Checksum)> {
+    let (hex, path) = line.split_once(char::is_whitespace)?;
+    let path = path.strip_prefix([' ', '*']).unwrap_or(path);
+    let checksum = Checksum::new(HashAlgorithm::from_hex_len(hex.len())?, hex).ok()?;
+    Some((base_name(path)?, checksum))
+}
+
+fn base_name(path: &str) -> Option<String> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod checksum_manifest_tests {
+    use super::*;
+
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn gnu_text_and_binary_modes() {
+        let content = format!("{SHA256}  text.iso\n{SHA256} *binary.iso\n");
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest.get("text.iso").unwrap().expected, SHA256);
+        assert_eq!(manifest.get("binary.iso").unwrap().expected, SHA256);
+    }
+
+    #[test]
+    fn gnu_algorithm_inferred_from_length() {
+        let sha512 = "a".repeat(128);
+        let content = format!("{sha512}  big.iso\n");
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        assert_eq!(
+            manifest.get("big.iso").unwrap().algorithm,
+            HashAlgorithm::Sha512
+        );
+    }
+
+    #[test]
+    fn bsd_format() {
+        let content = format!("SHA256 (release.tar.gz) = {SHA256}\n");
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        let checksum = manifest.get("release.tar.gz").unwrap();
+        assert_eq!(checksum.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(checksum.expected, SHA256);
+    }
+
+    #[test]
+    fn bsd_name_with_spaces_and_parentheses() {
+        let content = format!("SHA256 (my file (1).zip) = {SHA256}\n");
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        assert!(manifest.get("my file (1).zip").is_some());
+    }
+
+    #[test]
+    fn directories_are_stripped() {
+        let content = format!("{SHA256}  ./dist/app.deb\nSHA256 (out/lib.so) = {SHA256}\n");
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        assert!(manifest.get("app.deb").is_some());
+        assert!(manifest.get("lib.so").is_some());
+    }
+
+    #[test]
+    fn unsupported_and_malformed_lines_are_skipped() {
+        let content = format!(
+            "# comment\n\nMD5 (old.iso) = 900150983cd24fb0d6963f7d28e17f72\n\
+             900150983cd24fb0d6963f7d28e17f72  old.iso\nnot a checksum line\n{SHA256}  new.iso\n"
+        );
+        let manifest = ChecksumManifest::parse(&content).unwrap();
+        assert_eq!(manifest.len(), 1);
+        assert!(manifest.get("new.iso").is_some());
+    }
+
+    #[test]
+    fn manifest_without_supported_entries_is_an_error() {
+        assert!(ChecksumManifest::parse("900150983cd24fb0d6963f7d28e17f72  old.iso\n").is_err());
+        assert!(ChecksumManifest::parse("").is_err());
+    }
+}
@@ -70,6 +70,17 @@ pub fn make_client(config: &ClientConfig<'_>, redirect: bool) -> Result<Client,
     Ok(client_builder.build()?)
 }
 
+/// Fetch a small text resource, e.g. a checksum manifest, in one go.
+pub async fn fetch_text(client: &Client, url: &str) -> Result<String, DlmError> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(DlmError::ResponseStatusNotSuccess {
+            status_code: response.status().as_u16(),
+        });
+    }
+    Ok(response.text().await?)
+}
+
 fn build_default_headers(config: &ClientConfig<'_>) -> Result<HeaderMap, DlmError> {
     let mut headers = HeaderMap::new();
 
@@ -83,7 +94,15 @@ fn build_default_headers(config: &ClientConfig<'_>) -> Result<HeaderMap, DlmErro
         headers.insert(AUTHORIZATION, value);
     }
 
-    for (name, value) in config.headers {
+    headers.extend(header_map(config.headers)?);
+
+    Ok(headers)
+}
+
+/// Build the header map of `Name: Value` pairs given by the user.
+pub fn header_map(pairs: &[(String, String)]) -> Result<HeaderMap, DlmError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
         let header_name = name
             .parse::<HeaderName>()
             .map_err(|e| DlmError::CliArgumentError {
@@ -0,0 +1,215 @@
+use crate::file_link::FileLink;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// What to do with a download whose destination file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing file and skip the download.
+    Skip,
+    /// Replace the existing file once the download completes.
+    Overwrite,
+    /// Save the download under the first free `name.N.ext`.
+    Rename,
+    /// Move the existing file to `name.ext.bak` once the download completes.
+    Backup,
+}
+
+impl ConflictPolicy {
+    pub const NAMES: [&'static str; 4] = ["skip", "overwrite", "rename", "backup"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(Self::Skip),
+            "overwrite" => Some(Self::Overwrite),
+            "rename" => Some(Self::Rename),
+            "backup" => Some(Self::Backup),
+            _ => None,
+        }
+    }
+}
+
+/// First path `name.N.ext` next to `path` that is not taken, counting from 1.
+pub fn free_path(path: &Path, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (extension, stem) = FileLink::extract_extension_from_filename(&filename);
+    (1..)
+        .map(|index| {
+            let candidate = match &extension {
+                Some(extension) => format!("{stem}.{index}.{extension}"),
+                None => format!("{stem}.{index}"),
+            };
+            path.with_file_name(candidate)
+        })
+        .find(|candidate| !is_taken(candidate))
+        .expect("infinite candidates")
+}
+
+/// Destination paths claimed by the downloads of the batch. Two entries
+/// resolving to the same file would otherwise write the same `.part`, or
+/// the later one would take the file of the earlier one for its own.
+#[derive(Default)]
+pub struct Reservations {
+    paths: Mutex<HashSet<PathBuf>>,
+}
+
+impl Reservations {
+    /// Reserve `path` until the returned guard is dropped, or for the rest of
+    /// the batch once kept. Returns `None` if another download holds it.
+    pub fn reserve(&self, path: &Path) -> Option<Reservation<'_>> {
+        let mut paths = self.paths.lock().expect("reservations lock poisoned");
+        paths.insert(path.to_path_buf()).then(|| Reservation {
+            reservations: self,
+            path: path.to_path_buf(),
+            kept: false,
+        })
+    }
+
+    /// Whether a download of the batch is saving, or has saved, to `path`.
+    pub fn is_claimed(&self, path: &Path) -> bool {
+        let paths = self.paths.lock().expect("reservations lock poisoned");
+        paths.contains(path)
+    }
+
+    /// Reserve `path` if it is neither reserved nor existing, the first free
+    /// `name.N.ext` next to it otherwise.
+    pub fn reserve_free(&self, path: &Path) -> Reservation<'_> {
+        let mut paths = self.paths.lock().expect("reservations lock poisoned");
+        let is_taken = |candidate: &Path| paths.contains(candidate) || candidate.exists();
+        let path = if is_taken(path) {
+            free_path(path, is_taken)
+        } else {
+            path.to_path_buf()
+        };
+        paths.insert(path.clone());
+        Reservation {
+            reservations: self,
+            path,
+            kept: false,
+        }
+    }
+}
+
+/// A destination path held by a download, released on drop unless kept.
+pub struct Reservation<'a> {
+    reservations: &'a Reservations,
+    path: PathBuf,
+    kept: bool,
+}
+
+impl Reservation<'_> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep the path claimed for the rest of the batch, once the file of the
+    /// download is saved there.
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.kept {
+            return;
+        }
+        let mut paths = self
+            .reservations
+            .paths
+            .lock()
+            .expect("reservations lock poisoned");
+        paths.remove(&self.path);
+    }
+}
+
+/// Where `backup` moves an existing file, replacing any older backup.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod conflict_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn policy_names() {
+        for name in ConflictPolicy::NAMES {
+            assert!(ConflictPolicy::from_name(name).is_some(), "{name}");
+        }
+        assert_eq!(ConflictPolicy::from_name("merge"), None);
+    }
+
+    #[test]
+    fn free_path_numbers_before_extension() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("setup.exe");
+        std::fs::write(&path, "v1").unwrap();
+        assert_eq!(
+            free_path(&path, Path::exists),
+            dir.path().join("setup.1.exe")
+        );
+
+        std::fs::write(dir.path().join("setup.1.exe"), "v2").unwrap();
+        assert_eq!(
+            free_path(&path, Path::exists),
+            dir.path().join("setup.2.exe")
+        );
+    }
+
+    #[test]
+    fn free_path_without_extension() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("README");
+        assert_eq!(free_path(&path, Path::exists), dir.path().join("README.1"));
+    }
+
+    #[test]
+    fn reserve_is_exclusive_until_dropped() {
+        let reservations = Reservations::default();
+        let path = Path::new("/tmp/setup.exe");
+        let first = reservations.reserve(path).unwrap();
+        assert!(reservations.reserve(path).is_none());
+        drop(first);
+        assert!(reservations.reserve(path).is_some());
+    }
+
+    #[test]
+    fn kept_reservation_stays_claimed() {
+        let reservations = Reservations::default();
+        let path = Path::new("/tmp/setup.exe");
+        reservations.reserve(path).unwrap().keep();
+        assert!(reservations.is_claimed(path));
+        assert!(reservations.reserve(path).is_none());
+        assert_eq!(
+            reservations.reserve_free(path).path(),
+            Path::new("/tmp/setup.1.exe")
+        );
+    }
+
+    #[test]
+    fn reserve_free_skips_reserved_and_existing_paths() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("setup.exe");
+        let reservations = Reservations::default();
+
+        let first = reservations.reserve_free(&path);
+        assert_eq!(first.path(), path);
+        std::fs::write(dir.path().join("setup.1.exe"), "other").unwrap();
+        let second = reservations.reserve_free(&path);
+        assert_eq!(second.path(), dir.path().join("setup.2.exe"));
+    }
+
+    #[test]
+    fn backup_path_appends_suffix() {
+        let path = backup_path(Path::new("/tmp/file.bin"));
+        assert_eq!(path, PathBuf::from("/tmp/file.bin.bak"));
+    }
+}
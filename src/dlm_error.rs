@@ -16,6 +16,26 @@ pub enum DlmError {
     DeadLineElapsedTimeout,
     #[error("incomplete download - expected {expected} bytes, got {actual}")]
     IncompleteDownload { expected: u64, actual: u64 },
+    #[error("size mismatch - expected {expected} bytes, server announced {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("checksum mismatch - expected {algorithm} {expected}, got {actual}")]
+    ChecksumMismatch {
+        algorithm: String,
+        expected: String,
+        actual: String,
+    },
+    #[error(
+        "checksum verification failed for {} download(s):\n{}",
+        .links.len(),
+        .links.join("\n")
+    )]
+    ChecksumVerificationFailed { links: Vec<String> },
+    #[error("unexpected Content-Range '{content_range}' when requesting bytes from {offset}")]
+    ContentRangeMismatch { offset: u64, content_range: String },
+    #[error("another download of this batch saves to {path}")]
+    DestinationInUse { path: String },
+    #[error("{path} is being downloaded by another dlm process (pid {pid})")]
+    PartLocked { path: String, pid: String },
     #[error("response status not success - {status_code}")]
     ResponseStatusNotSuccess { status_code: u16 },
     #[error("standard I/O error - {e}")]
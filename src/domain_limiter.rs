@@ -0,0 +1,92 @@
+use crate::file_link::FileLink;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps the number of simultaneous downloads per URL host.
+///
+/// Every host gets its own semaphore, created on first use, so downloads
+/// waiting for a busy host never delay downloads for other hosts.
+pub struct DomainLimiter {
+    max_per_domain: usize,
+    // `std::sync::Mutex` is fine here — the lock is never held across an `.await`
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl DomainLimiter {
+    pub fn new(max_per_domain: u32) -> Self {
+        Self {
+            max_per_domain: max_per_domain as usize,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a download slot on the host of `link`. The slot is freed when
+    /// the returned permit is dropped. Links whose host cannot be parsed are
+    /// not gated, the download itself reports the invalid URL.
+    pub async fn acquire(&self, link: &str) -> Option<OwnedSemaphorePermit> {
+        let host = FileLink::new(link).ok()?.host;
+        let semaphore = self
+            .semaphores
+            .lock()
+            .expect("domain limiter lock should not be poisoned")
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_domain)))
+            .clone();
+        // the semaphore is never closed
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod domain_limiter_tests {
+    use super::*;
+    use tokio::time::{Duration, timeout};
+
+    #[tokio::test]
+    async fn same_host_waits_for_a_free_slot() {
+        let limiter = DomainLimiter::new(1);
+        let first = limiter.acquire("https://a.example.com/one.bin").await;
+        assert!(first.is_some());
+
+        let second = timeout(
+            Duration::from_millis(50),
+            limiter.acquire("https://a.example.com/two.bin"),
+        )
+        .await;
+        assert!(
+            second.is_err(),
+            "second download on the same host must wait"
+        );
+
+        drop(first);
+        let third = timeout(
+            Duration::from_millis(50),
+            limiter.acquire("https://a.example.com/three.bin"),
+        )
+        .await;
+        assert!(
+            third.is_ok(),
+            "slot should be free once the permit is dropped"
+        );
+    }
+
+    #[tokio::test]
+    async fn other_hosts_are_not_blocked() {
+        let limiter = DomainLimiter::new(1);
+        let _busy = limiter.acquire("https://a.example.com/one.bin").await;
+
+        let other = timeout(
+            Duration::from_millis(50),
+            limiter.acquire("https://b.example.com/one.bin"),
+        )
+        .await;
+        assert!(other.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unparseable_link_is_not_gated() {
+        let limiter = DomainLimiter::new(1);
+        assert!(limiter.acquire("not-a-url").await.is_none());
+    }
+}
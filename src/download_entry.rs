@@ -0,0 +1,343 @@
+use crate::args::parse_header;
+use crate::checksum::Checksum;
+use crate::dlm_error::DlmError;
+use crate::file_link::sanitize_relative_dir;
+use crate::json_input;
+use std::path::{Path, PathBuf};
+
+/// A URL to download together with the settings that came with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadEntry {
+    pub url: String,
+    /// Other URLs serving the same file, tried in order after `url` on
+    /// retryable errors.
+    pub mirrors: Vec<String>,
+    /// Name to save the file under instead of the one derived from the URL.
+    pub filename: Option<String>,
+    /// Directory to save the file in, relative to the output directory.
+    pub dir: Option<PathBuf>,
+    /// Request headers sent on top of the ones of the whole run.
+    pub headers: Vec<(String, String)>,
+    /// Expected size of the downloaded file in bytes.
+    pub size: Option<u64>,
+    /// Expected digest of the downloaded file.
+    pub checksum: Option<Checksum>,
+    /// Order of the download in a JSON input file, the lowest first. Only
+    /// applied when the whole file is read up front.
+    pub priority: Option<u32>,
+    /// Position of the entry in the input from 1, e.g. its line number in
+    /// an input file.
+    pub index: usize,
+}
+
+impl DownloadEntry {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim().to_string(),
+            mirrors: Vec::new(),
+            filename: None,
+            dir: None,
+            headers: Vec::new(),
+            size: None,
+            checksum: None,
+            priority: None,
+            index: 1,
+        }
+    }
+
+    /// `url` then the mirrors, in the order they are tried.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url.as_str()).chain(self.mirrors.iter().map(String::as_str))
+    }
+
+    /// Parse an input line: a URL optionally followed by whitespace and a
+    /// checksum, e.g. `https://host/file.iso sha256=9f86d0...`.
+    ///
+    /// Only a trailing token shaped like `algorithm=hex` is taken as a
+    /// checksum, so query strings such as `?a=b` remain part of the URL.
+    pub fn parse(line: &str) -> Result<Self, DlmError> {
+        let line = line.trim();
+        if let Some((url, last)) = line.rsplit_once(char::is_whitespace)
+            && looks_like_checksum(last)
+        {
+            let mut entry = Self::new(url);
+            entry.checksum = Some(Checksum::parse(last)?);
+            return Ok(entry);
+        }
+        Ok(Self::new(line))
+    }
+
+    /// Parse a line of an input file that is not an option: a URL line or a
+    /// JSON object.
+    pub fn parse_line(line: &str) -> Result<Self, DlmError> {
+        if line.trim_start().starts_with('{') {
+            json_input::parse_entry(line)
+        } else {
+            Self::parse(line)
+        }
+    }
+
+    /// Apply an option line given under the URL in the input file, e.g.
+    /// `out=renamed.zip`.
+    pub fn apply_option(&mut self, line: &str) -> Result<(), DlmError> {
+        let (key, value) = line
+            .trim()
+            .split_once('=')
+            .ok_or_else(|| DlmError::other(format!("invalid option '{}'", line.trim())))?;
+        let value = value.trim();
+        match key {
+            "out" => self.filename = Some(value.to_string()),
+            "dir" => self.dir = Some(sanitize_relative_dir(Path::new(value))),
+            "header" => self.headers.push(parse_header(value)?),
+            "referer" => self
+                .headers
+                .push(("Referer".to_string(), value.to_string())),
+            "checksum" => self.checksum = Some(Checksum::parse(value)?),
+            _ => return Err(DlmError::other(format!("unknown option '{key}'"))),
+        }
+        Ok(())
+    }
+}
+
+/// Blank line or `#` comment of an input file, skipped.
+pub fn is_empty_line(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Indented `key=value` line holding an option of the URL above it. An
+/// indented URL is not one: its part before `=` is not a plain word.
+pub fn is_option_line(line: &str) -> bool {
+    line.starts_with([' ', '\t'])
+        && line
+            .trim_start()
+            .split_once('=')
+            .is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+/// Group the lines of an input file into entries: a URL line followed by
+/// its option lines, or a JSON object line. Comments and blank lines must be
+/// filtered out first.
+#[derive(Default)]
+pub struct EntryParser {
+    current: Option<Result<DownloadEntry, String>>,
+}
+
+impl EntryParser {
+    /// Feed the next line, numbered from 1. Returns the previous entry once a
+    /// new URL line shows it has no more options. Errors are the messages to
+    /// log.
+    pub fn push_line(
+        &mut self,
+        line_number: usize,
+        line: &str,
+    ) -> Option<Result<DownloadEntry, String>> {
+        if !is_option_line(line) {
+            let entry = DownloadEntry::parse_line(line)
+                .map(|entry| DownloadEntry {
+                    index: line_number,
+                    ..entry
+                })
+                .map_err(|e| format!("Error for {}: {e}", line.trim()));
+            return self.current.replace(entry);
+        }
+        match &mut self.current {
+            Some(Ok(entry)) => {
+                if let Err(e) = entry.apply_option(line) {
+                    let message = format!("Error for {}: {e}", entry.url);
+                    self.current = Some(Err(message));
+                }
+                None
+            }
+            // the options of an invalid entry are dropped with it
+            Some(Err(_)) => None,
+            None => Some(Err(format!(
+                "Error for option '{}': no URL above it",
+                line.trim()
+            ))),
+        }
+    }
+
+    /// The entry being read, without waiting for the line after it. Option
+    /// lines fed afterwards have no URL above them.
+    pub fn flush(&mut self) -> Option<Result<DownloadEntry, String>> {
+        self.current.take()
+    }
+
+    /// The last entry, once all lines have been fed.
+    pub fn finish(self) -> Option<Result<DownloadEntry, String>> {
+        self.current
+    }
+}
+
+/// `algorithm=hex` where the algorithm name starts with `sha`.
+fn looks_like_checksum(token: &str) -> bool {
+    token
+        .split_once('=')
+        .is_some_and(|(name, _)| name.to_ascii_lowercase().starts_with("sha"))
+}
+
+#[cfg(test)]
+mod download_entry_tests {
+    use super::*;
+    use crate::checksum::HashAlgorithm;
+
+    const DIGEST: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn plain_url() {
+        let entry = DownloadEntry::parse("  https://example.com/file.bin  ").unwrap();
+        assert_eq!(entry.url, "https://example.com/file.bin");
+        assert_eq!(entry.checksum, None);
+    }
+
+    #[test]
+    fn url_with_checksum() {
+        let line = format!("https://example.com/file.bin   sha256={DIGEST}");
+        let entry = DownloadEntry::parse(&line).unwrap();
+        assert_eq!(entry.url, "https://example.com/file.bin");
+        let checksum = entry.checksum.unwrap();
+        assert_eq!(checksum.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(checksum.expected, DIGEST);
+    }
+
+    #[test]
+    fn query_string_is_not_a_checksum() {
+        let entry = DownloadEntry::parse("https://example.com/get?sha=1").unwrap();
+        assert_eq!(entry.url, "https://example.com/get?sha=1");
+        assert_eq!(entry.checksum, None);
+    }
+
+    #[test]
+    fn urls_start_with_the_url_then_the_mirrors() {
+        let mut entry = DownloadEntry::new("https://a.example.com/file.bin");
+        assert!(entry.urls().eq(["https://a.example.com/file.bin"]));
+
+        entry.mirrors = vec![
+            "https://b.example.com/file.bin".to_string(),
+            "https://c.example.com/file.bin".to_string(),
+        ];
+        assert!(entry.urls().eq([
+            "https://a.example.com/file.bin",
+            "https://b.example.com/file.bin",
+            "https://c.example.com/file.bin"
+        ]));
+    }
+
+    fn parse_all(lines: &[&str]) -> Vec<Result<DownloadEntry, String>> {
+        let mut parser = EntryParser::default();
+        let mut entries: Vec<_> = lines
+            .iter()
+            .zip(1..)
+            .filter_map(|(line, number)| parser.push_line(number, line))
+            .collect();
+        entries.extend(parser.finish());
+        entries
+    }
+
+    #[test]
+    fn option_lines_apply_to_the_url_above() {
+        let checksum = format!("  checksum=sha256={DIGEST}");
+        let entries = parse_all(&[
+            "https://example.com/a.bin",
+            "  out=renamed.bin",
+            "\tdir=sub/dir",
+            "  header=X-Token: abc",
+            "  referer=https://example.com/page?x=1",
+            &checksum,
+            "https://example.com/b.bin",
+        ]);
+        assert_eq!(entries.len(), 2);
+        let a = entries[0].as_ref().unwrap();
+        assert_eq!(a.filename.as_deref(), Some("renamed.bin"));
+        assert_eq!(a.dir, Some(PathBuf::from("sub/dir")));
+        assert_eq!(
+            a.headers,
+            vec![
+                ("X-Token".to_string(), "abc".to_string()),
+                (
+                    "Referer".to_string(),
+                    "https://example.com/page?x=1".to_string()
+                ),
+            ]
+        );
+        assert_eq!(a.checksum.as_ref().unwrap().expected, DIGEST);
+        let b = DownloadEntry {
+            index: 7,
+            ..DownloadEntry::new("https://example.com/b.bin")
+        };
+        assert_eq!(entries[1], Ok(b));
+    }
+
+    #[test]
+    fn dir_option_stays_relative() {
+        let entries = parse_all(&[
+            "https://example.com/a.bin",
+            "  dir=../x",
+            "https://example.com/b.bin",
+            "  dir=/abs",
+        ]);
+        assert_eq!(entries[0].as_ref().unwrap().dir, Some(PathBuf::from("x")));
+        assert_eq!(entries[1].as_ref().unwrap().dir, Some(PathBuf::from("abs")));
+    }
+
+    #[test]
+    fn indented_url_is_not_an_option() {
+        assert!(is_option_line("  out=file.bin"));
+        assert!(!is_option_line("out=file.bin"));
+        assert!(!is_option_line("  https://example.com/get?id=1"));
+        let entries = parse_all(&["https://example.com/a.bin", "  https://example.com/b?id=1"]);
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn invalid_option_fails_its_entry_only() {
+        let entries = parse_all(&[
+            "https://example.com/a.bin",
+            "  color=blue",
+            "  out=ignored.bin",
+            "https://example.com/b.bin",
+        ]);
+        let message = entries[0].as_ref().unwrap_err();
+        assert!(message.contains("https://example.com/a.bin"), "{message}");
+        assert!(message.contains("unknown option 'color'"), "{message}");
+        assert!(entries[1].is_ok());
+    }
+
+    #[test]
+    fn option_without_url_is_an_error() {
+        let entries = parse_all(&["  out=orphan.bin", "https://example.com/a.bin"]);
+        assert!(entries[0].as_ref().unwrap_err().contains("no URL above it"));
+        assert!(entries[1].is_ok());
+    }
+
+    #[test]
+    fn json_line_is_an_entry() {
+        let entries = parse_all(&[
+            r#"{"url": "https://example.com/a.bin", "filename": "b.bin"}"#,
+            "https://example.com/c.bin",
+        ]);
+        let a = entries[0].as_ref().unwrap();
+        assert_eq!(a.filename.as_deref(), Some("b.bin"));
+        assert_eq!(a.index, 1);
+        assert!(entries[1].is_ok());
+    }
+
+    #[test]
+    fn flush_yields_the_pending_entry() {
+        let mut parser = EntryParser::default();
+        assert!(parser.push_line(1, "https://example.com/a.bin").is_none());
+        assert!(parser.push_line(2, "  out=a.bin").is_none());
+        let entry = parser.flush().unwrap().unwrap();
+        assert_eq!(entry.filename.as_deref(), Some("a.bin"));
+        assert!(parser.flush().is_none());
+        let orphan = parser.push_line(3, "  out=late.bin").unwrap();
+        assert!(orphan.unwrap_err().contains("no URL above it"));
+    }
+
+    #[test]
+    fn invalid_checksum_is_an_error() {
+        assert!(DownloadEntry::parse("https://example.com/file.bin sha256=nope").is_err());
+        assert!(DownloadEntry::parse("https://example.com/file.bin sha1=abc").is_err());
+    }
+}
@@ -1,28 +1,78 @@
+use futures_util::future::try_join_all;
 use indicatif::ProgressBar;
+use jiff::Timestamp;
+use jiff::civil::Date;
+use jiff::tz::TimeZone;
 use reqwest::Client;
-use reqwest::header::RANGE;
+use reqwest::header::{
+    CONTENT_RANGE, HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE,
+};
 use std::cmp::Ordering;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use tokio::io::AsyncWriteExt;
 use tokio::time::{Duration, timeout};
 use tokio::{fs as tfs, select};
 use tokio_util::sync::CancellationToken;
 
 use crate::ProgressBarManager;
-use crate::client::{ClientConfig, make_client};
+use crate::bandwidth::RateLimiter;
+use crate::checksum::{Checksum, Hasher};
+use crate::checksum_manifest::ChecksumManifest;
+use crate::client::{ClientConfig, header_map, make_client};
+use crate::conflict::{ConflictPolicy, Reservations, backup_path};
 use crate::dlm_error::DlmError;
-use crate::file_link::FileLink;
+use crate::download_entry::DownloadEntry;
+use crate::file_link::{FileLink, sanitize_relative_dir};
 use crate::headers::{
-    content_disposition_value, content_length_value, location_value, parse_filename_header,
+    Metadata, content_disposition_value, content_length_value, content_range_start, etag_value,
+    http_date, last_modified_value, location_value, parse_filename_header, parse_http_date,
     parse_metadata_from, supports_range_bytes,
 };
+use crate::part_lock::PartLock;
+use crate::segment::{merge_segments, remove_segments, segment_path, split_ranges};
+use crate::sidecar::Sidecar;
+use crate::template::{OutputTemplate, TemplateValues};
 use crate::utils::pretty_bytes_size;
 
+/// Download settings shared by every download of a run.
+pub struct DownloadConfig {
+    /// Number of byte ranges fetched concurrently per file.
+    pub segments: u32,
+    /// Cap in bytes per second shared by all downloads.
+    pub max_bandwidth: Option<u64>,
+    /// Cap in bytes per second applied to each download on its own.
+    pub max_bandwidth_per_download: Option<u64>,
+    /// Expected checksums by filename, for entries without their own.
+    pub checksum_manifest: Option<ChecksumManifest>,
+    /// Download existing files again when the remote file changed.
+    pub update: bool,
+    /// Date the downloaded files from their `Last-Modified` header.
+    pub remote_time: bool,
+    /// What to do when the destination file already exists.
+    pub on_conflict: ConflictPolicy,
+    /// Save each file under `host/path/` in the output directory.
+    pub keep_paths: bool,
+    /// Destination of each file relative to the output directory.
+    pub output_template: Option<OutputTemplate>,
+}
+
 pub struct DownloadContext<'a> {
     client: Client,
     client_no_redirect: Client,
     connection_timeout_secs: u32,
     read_timeout_secs: u32,
+    segments: u32,
+    bandwidth_limiter: Option<RateLimiter>,
+    max_bandwidth_per_download: Option<u64>,
+    checksum_manifest: Option<ChecksumManifest>,
+    update: bool,
+    remote_time: bool,
+    on_conflict: ConflictPolicy,
+    keep_paths: bool,
+    output_template: Option<OutputTemplate>,
+    /// Destination paths of the downloads in progress.
+    reservations: Reservations,
     output_dir: &'a Path,
     token: &'a CancellationToken,
     pb_manager: &'a ProgressBarManager,
@@ -31,6 +81,7 @@ pub struct DownloadContext<'a> {
 impl<'a> DownloadContext<'a> {
     pub fn new(
         client_config: &ClientConfig<'_>,
+        download_config: DownloadConfig,
         output_dir: &'a Path,
         token: &'a CancellationToken,
         pb_manager: &'a ProgressBarManager,
@@ -40,29 +91,58 @@ impl<'a> DownloadContext<'a> {
             client_no_redirect: make_client(client_config, false)?,
             connection_timeout_secs: client_config.connection_timeout_secs,
             read_timeout_secs: client_config.read_timeout_secs,
+            segments: download_config.segments,
+            bandwidth_limiter: download_config.max_bandwidth.map(RateLimiter::new),
+            max_bandwidth_per_download: download_config.max_bandwidth_per_download,
+            checksum_manifest: download_config.checksum_manifest,
+            update: download_config.update,
+            remote_time: download_config.remote_time,
+            on_conflict: download_config.on_conflict,
+            keep_paths: download_config.keep_paths,
+            output_template: download_config.output_template,
+            reservations: Reservations::default(),
             output_dir,
             token,
             pb_manager,
         })
     }
 
-    /// Extract download metadata (content-length, range support, disposition filename).
+    /// Directory the file of `entry` is saved in, fetched from `file_link`.
+    /// The directory of the entry comes from the input and is kept under
+    /// the output directory whatever it holds.
+    fn entry_output_dir(&self, entry: &DownloadEntry, file_link: &FileLink) -> PathBuf {
+        let output_dir = match &entry.dir {
+            Some(dir) => self.output_dir.join(sanitize_relative_dir(dir)),
+            None => self.output_dir.to_path_buf(),
+        };
+        if self.keep_paths {
+            output_dir.join(file_link.url_directory())
+        } else {
+            output_dir
+        }
+    }
+
+    /// Extract download metadata (content-length, range support, disposition
+    /// filename, validators).
     ///
     /// HEAD first. The disposition filename always comes from HEAD when HEAD
     /// succeeded; otherwise from the ranged-GET probe.
-    async fn extract_metadata(
-        &self,
-        url: &str,
-    ) -> Result<(Option<u64>, bool, Option<String>), DlmError> {
-        let head = self.client.head(url).send().await?;
+    async fn extract_metadata(&self, source: &Source) -> Result<Metadata, DlmError> {
+        let url = source.url.as_str();
+        let head = self
+            .client
+            .head(url)
+            .headers(source.headers.clone())
+            .send()
+            .await?;
         let head_status = head.status();
 
-        // HEAD outright rejected → derive the whole triple from a ranged GET.
+        // HEAD outright rejected → derive all the metadata from a ranged GET.
         if head_status == reqwest::StatusCode::METHOD_NOT_ALLOWED {
             self.pb_manager.log_above_progress_bars(&format!(
                 "HEAD returned 405 for {url}, falling back to GET for metadata"
             ));
-            return self.metadata_from_probe(url).await;
+            return self.metadata_from_probe(source).await;
         }
 
         if !head_status.is_success() {
@@ -71,28 +151,30 @@ impl<'a> DownloadContext<'a> {
             });
         }
 
-        // HEAD succeeded — the disposition filename is taken from it.
-        let disposition = content_disposition_value(head.headers()).and_then(parse_filename_header);
-
         // For length + range support: probe with a ranged GET when HEAD claims
         // `Content-Length: 0` (a sign HEAD is faked); trust HEAD when it
         // reports a real length; give up when no header is present.
-        let (length, supports_range) = match content_length_value(head.headers()) {
-            Some(0) => self.length_and_range_from_probe(url).await?,
+        let (content_length, supports_range) = match content_length_value(head.headers()) {
+            Some(0) => self.length_and_range_from_probe(source).await?,
             Some(n) => (Some(n), supports_range_bytes(head.headers())),
             None => (None, false),
         };
 
-        Ok((length, supports_range, disposition))
+        // HEAD succeeded — the disposition filename and validators are taken from it.
+        Ok(Metadata {
+            content_length,
+            supports_range,
+            disposition_filename: content_disposition_value(head.headers())
+                .and_then(parse_filename_header),
+            etag: etag_value(head.headers()),
+            last_modified: last_modified_value(head.headers()),
+        })
     }
 
-    /// Full-triple fallback used when HEAD is outright rejected (405).
+    /// Full-metadata fallback used when HEAD is outright rejected (405).
     /// A failed probe is fatal here because we have no other source of metadata.
-    async fn metadata_from_probe(
-        &self,
-        url: &str,
-    ) -> Result<(Option<u64>, bool, Option<String>), DlmError> {
-        let probe = self.range_probe(url).await?;
+    async fn metadata_from_probe(&self, source: &Source) -> Result<Metadata, DlmError> {
+        let probe = self.range_probe(source).await?;
         if !probe.status().is_success() {
             return Err(DlmError::ResponseStatusNotSuccess {
                 status_code: probe.status().as_u16(),
@@ -106,43 +188,91 @@ impl<'a> DownloadContext<'a> {
     /// on length/range, keeping the disposition filename from HEAD.
     async fn length_and_range_from_probe(
         &self,
-        url: &str,
+        source: &Source,
     ) -> Result<(Option<u64>, bool), DlmError> {
-        let probe = self.range_probe(url).await?;
+        let probe = self.range_probe(source).await?;
         if !probe.status().is_success() {
             self.pb_manager.log_above_progress_bars(&format!(
-                "GET fallback for metadata returned {} for {url}, proceeding without content-length",
-                probe.status()
+                "GET fallback for metadata returned {} for {}, proceeding without content-length",
+                probe.status(),
+                source.url
             ));
             return Ok((None, false));
         }
-        let (length, supports_range, _) = parse_metadata_from(probe.headers());
-        Ok((length, supports_range))
+        let metadata = parse_metadata_from(probe.headers());
+        Ok((metadata.content_length, metadata.supports_range))
     }
 
     /// Single-byte ranged GET used to coax metadata out of servers that don't
     /// answer HEAD properly. Returns the raw response for header inspection.
-    async fn range_probe(&self, url: &str) -> Result<reqwest::Response, DlmError> {
+    async fn range_probe(&self, source: &Source) -> Result<reqwest::Response, DlmError> {
         Ok(self
             .client
-            .get(url)
+            .get(&source.url)
+            .headers(source.headers.clone())
             .header(RANGE, "bytes=0-0")
             .send()
             .await?)
     }
 
+    /// Ask the server whether the file it serves is still the one saved at
+    /// `final_file_path`, with a conditional HEAD based on the `ETag` kept
+    /// next to it and on its modification time. A server not answering 304
+    /// is judged on `metadata` against the sidecar instead. `None` when
+    /// neither tells.
+    async fn is_up_to_date(
+        &self,
+        source: &Source,
+        final_file_path: &Path,
+        content_length: Option<u64>,
+        metadata: &Metadata,
+    ) -> Result<Option<bool>, DlmError> {
+        let sidecar = Sidecar::load(final_file_path).await;
+        let mut request = self
+            .client
+            .head(&source.url)
+            .headers(source.headers.clone());
+        if let Some(etag) = sidecar.as_ref().and_then(|s| s.etag.as_deref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let modified = tfs::metadata(final_file_path).await?.modified()?;
+        if let Some(date) = http_date(modified) {
+            request = request.header(IF_MODIFIED_SINCE, date);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Some(true));
+        }
+        // conditional requests ignored or HEAD refused (405, 501): the
+        // metadata of the download tells instead
+        Ok(sidecar.and_then(|s| s.is_current(content_length, metadata)))
+    }
+
+    /// Download `entry` from `url`, which is either its URL or one of its mirrors.
+    /// `resumed_bytes` is set to the size of the partial data found on disk,
+    /// counted by `pb_dl` but not transferred by this download.
     pub async fn download_link(
         &self,
-        raw_link: &str,
+        entry: &DownloadEntry,
+        url: &str,
         pb_dl: &ProgressBar,
+        resumed_bytes: &AtomicU64,
     ) -> Result<String, DlmError> {
-        let file_link = FileLink::new(raw_link)?;
+        resumed_bytes.store(0, AtomicOrdering::Relaxed);
+        let mut file_link = FileLink::new(url)?;
+        if let Some(filename) = &entry.filename {
+            file_link.set_filename(filename)?;
+        }
 
-        // When the filename is fully known from the URL, skip the HEAD request if the file exists
-        if file_link.extension.is_some() {
+        // When the filename is fully known upfront, skip the HEAD request if the file exists
+        // a template may depend on the response, the path is only known after HEAD
+        let skips_existing = !self.update
+            && self.on_conflict == ConflictPolicy::Skip
+            && self.output_template.is_none();
+        if skips_existing && (file_link.extension.is_some() || entry.filename.is_some()) {
             let filename = file_link.filename();
-            let final_file_path = self.output_dir.join(&filename);
-            if final_file_path.exists() {
+            let final_file_path = self.entry_output_dir(entry, &file_link).join(&filename);
+            if final_file_path.exists() && !self.reservations.is_claimed(&final_file_path) {
                 return already_completed_message(&final_file_path, &filename).await;
             }
         }
@@ -150,32 +280,114 @@ impl<'a> DownloadContext<'a> {
         // select between stop signal and download
         select! {
             () = self.token.cancelled() => Err(DlmError::ProgramInterrupted),
-            dl = self.download(file_link, pb_dl) => dl,
+            dl = self.download(file_link, entry, pb_dl, resumed_bytes) => dl,
         }
     }
 
     async fn download(
         &self,
         mut file_link: FileLink,
+        entry: &DownloadEntry,
         pb_dl: &ProgressBar,
+        resumed_bytes: &AtomicU64,
     ) -> Result<String, DlmError> {
+        let source = Source {
+            url: file_link.url.clone(),
+            headers: header_map(&entry.headers)?,
+        };
+
         // extract metadata with a HEAD request, falling back to GET if needed
-        let (content_length, supports_range, disposition_filename) =
-            self.extract_metadata(&file_link.url).await?;
+        let metadata = self.extract_metadata(&source).await?;
+        let supports_range = metadata.supports_range;
+
+        // the size declared by the input, if any, must match the one announced
+        let content_length = match (metadata.content_length, entry.size) {
+            (Some(actual), Some(expected)) if actual != expected => {
+                return Err(DlmError::SizeMismatch { expected, actual });
+            }
+            (content_length, size) => content_length.or(size),
+        };
 
         // resolve filename and extension if not already known from the URL
-        if file_link.extension.is_none() {
-            self.resolve_filename(&mut file_link, disposition_filename)
-                .await?;
+        if file_link.extension.is_none() && entry.filename.is_none() {
+            self.resolve_filename(
+                &mut file_link,
+                &source,
+                metadata.disposition_filename.clone(),
+            )
+            .await?;
+        }
+
+        let mut filename = file_link.filename();
+        let mut output_dir = self.entry_output_dir(entry, &file_link);
+
+        // a checksum given next to the URL wins over the manifest
+        let checksum = entry
+            .checksum
+            .as_ref()
+            .or_else(|| self.checksum_manifest.as_ref()?.get(&filename));
+
+        // the template lays out the destination once the name is resolved
+        if let Some(template) = &self.output_template {
+            let values = TemplateValues {
+                host: &file_link.host,
+                name: &file_link.filename_without_extension,
+                ext: file_link.extension.as_deref(),
+                index: entry.index,
+                date: remote_date(&metadata),
+            };
+            let path = output_dir.join(template.expand(&values)?);
+            filename = file_name_of(&path);
+            output_dir = path.parent().map(Path::to_path_buf).unwrap_or(output_dir);
         }
+        let mut final_file_path = output_dir.join(&filename);
 
-        let filename = file_link.filename();
-        let output_dir = self.output_dir;
-        let final_file_path = output_dir.join(&filename);
+        // the destination exists (checked again here for the case where the
+        // filename was resolved via headers). A file saved by another entry
+        // of the batch is not this one, it is handled by the reservation.
+        if final_file_path.exists() && !self.reservations.is_claimed(&final_file_path) {
+            if self.update {
+                let up_to_date = self
+                    .is_up_to_date(&source, &final_file_path, content_length, &metadata)
+                    .await?;
+                let message = match up_to_date {
+                    Some(true) => {
+                        return up_to_date_message(&final_file_path, &filename).await;
+                    }
+                    Some(false) => {
+                        format!("The remote file {filename} changed, downloading the new version")
+                    }
+                    None => format!(
+                        "Can't tell whether the remote file {filename} changed, downloading it again"
+                    ),
+                };
+                self.pb_manager.log_above_progress_bars(&message);
+            }
+            match self.on_conflict {
+                ConflictPolicy::Skip => {
+                    return already_completed_message(&final_file_path, &filename).await;
+                }
+                // the new file is saved under the free name reserved below
+                ConflictPolicy::Rename => {}
+                // the existing file is dealt with once the download completes
+                ConflictPolicy::Overwrite | ConflictPolicy::Backup => {}
+            }
+        }
 
-        // skip completed download (needed for the case where filename was resolved via headers)
-        if final_file_path.exists() {
-            return already_completed_message(&final_file_path, &filename).await;
+        // two entries of the batch resolving to the same file must not share
+        // a `.part` or a destination: the duplicate gets another name or fails
+        let reservation = if self.on_conflict == ConflictPolicy::Rename {
+            self.reservations.reserve_free(&final_file_path)
+        } else {
+            self.reservations.reserve(&final_file_path).ok_or_else(|| {
+                DlmError::DestinationInUse {
+                    path: final_file_path.display().to_string(),
+                }
+            })?
+        };
+        if reservation.path() != final_file_path {
+            final_file_path = reservation.path().to_path_buf();
+            filename = file_name_of(&final_file_path);
         }
 
         // setup progress bar for the file
@@ -184,43 +396,251 @@ impl<'a> DownloadContext<'a> {
             pb_dl.set_length(total_size);
         }
 
+        // the directory given for the entry or mirroring the URL may not exist yet
+        if output_dir != self.output_dir {
+            tfs::create_dir_all(&output_dir).await?;
+        }
+
         let tmp_name = output_dir.join(format!("{filename}.part"));
+
+        // another dlm process sharing the output directory may be writing it
+        let _lock = PartLock::acquire(&tmp_name).await?;
+
+        // partial data fetched from another version of the resource cannot
+        // be completed, it is thrown away. Mirrors rarely share validators,
+        // so switching to one with its own restarts the download as well.
+        let sidecar = match Sidecar::load(&tmp_name).await {
+            Some(stored) if stored.is_outdated(content_length, &metadata) => {
+                let message = if stored.url == file_link.url {
+                    format!(
+                        "The remote file {filename} changed since its download started, restarting from scratch"
+                    )
+                } else {
+                    format!(
+                        "The partial download of {filename} came from another mirror, restarting from scratch"
+                    )
+                };
+                self.pb_manager.log_above_progress_bars(&message);
+                if tmp_name.exists() {
+                    tfs::remove_file(&tmp_name).await?;
+                }
+                let segment_count = stored.segments.len().max(self.segments as usize);
+                remove_segments(&tmp_name, segment_count).await?;
+                None
+            }
+            stored => stored,
+        };
+        let stored_validator = sidecar
+            .as_ref()
+            .and_then(Sidecar::validator)
+            .map(ToString::to_string);
+
         let resume_action = compute_resume_action(
             pb_dl,
             self.pb_manager,
             content_length,
             supports_range,
             &tmp_name,
+            stored_validator,
         )
         .await?;
 
         // Fast-path: the .part already holds the complete body (e.g. a prior
         // run was killed between the last chunk and the rename). Finalize it
         // without issuing a GET.
+        // the bar starts from the resumed `.part`, if any
+        resumed_bytes.store(pb_dl.position(), AtomicOrdering::Relaxed);
+
         if matches!(resume_action, ResumeAction::AlreadyComplete) {
+            if let Some(checksum) = checksum {
+                checksum.verify_file(&tmp_name).await?;
+            }
             let final_file_size = tfs::metadata(&tmp_name).await?.len();
-            return finalize_download(&tmp_name, &final_file_path, &filename, final_file_size)
-                .await;
+            let message = self
+                .finalize_download(&tmp_name, final_file_path, final_file_size)
+                .await?;
+            reservation.keep();
+            return Ok(message);
         }
 
-        // create/open file.part
-        // no need for a BufWriter because the HTTP chunks are rather large
-        let mut file = match &resume_action {
-            ResumeAction::Resume(_) => {
-                tfs::OpenOptions::new()
-                    .append(true)
-                    .create(false)
-                    .open(&tmp_name)
+        // Split the body over several connections when asked to and the server
+        // serves ranges. A `.part` being resumed keeps its single stream.
+        let segmented = content_length.filter(|_| {
+            self.segments > 1 && supports_range && matches!(resume_action, ResumeAction::Fresh)
+        });
+
+        // describe the `.part` next to it until the download completes
+        let mut sidecar =
+            sidecar.unwrap_or_else(|| Sidecar::new(&file_link.url, content_length, &metadata));
+        sidecar.url.clone_from(&file_link.url);
+        if matches!(resume_action, ResumeAction::Fresh) {
+            sidecar.content_length = content_length;
+            sidecar.reset_validators(&metadata);
+        }
+        // leftover segment files only fit the ranges they were started with
+        sidecar.segments = match segmented {
+            Some(total)
+                if sidecar
+                    .segments
+                    .last()
+                    .is_some_and(|(_, end)| end + 1 == total) =>
+            {
+                sidecar.segments
+            }
+            Some(total) => split_ranges(total, self.segments),
+            // a single stream has no use for the segment files of a previous run
+            None => {
+                remove_segments(&tmp_name, sidecar.segments.len()).await?;
+                Vec::new()
+            }
+        };
+        sidecar.save(&tmp_name).await?;
+
+        // shared by all the segments of this download
+        let dl_limiter = self.max_bandwidth_per_download.map(RateLimiter::new);
+        let dl_limiter = dl_limiter.as_ref();
+
+        // Digest fed while the body streams in. Segments arrive out of order,
+        // so their merged `.part` is hashed once complete instead.
+        let mut hasher = None;
+
+        let final_file_size = match segmented {
+            Some(_) => {
+                // each segment counts its own bytes, a failed attempt must
+                // not leave its position on the bar
+                pb_dl.set_position(0);
+                let mut resumed = 0;
+                for (index, &range) in sidecar.segments.iter().enumerate() {
+                    resumed += existing_segment_len(&segment_path(&tmp_name, index), range).await;
+                }
+                resumed_bytes.store(resumed, AtomicOrdering::Relaxed);
+                self.download_segments(&source, &tmp_name, &sidecar.segments, pb_dl, dl_limiter)
                     .await?
             }
-            // Fresh: truncate any stale .part (AlreadyComplete handled above).
-            _ => tfs::File::create(&tmp_name).await?,
+            None => {
+                hasher = checksum.map(Checksum::hasher);
+                self.download_stream(
+                    &source,
+                    &tmp_name,
+                    &resume_action,
+                    pb_dl,
+                    dl_limiter,
+                    hasher.as_mut(),
+                )
+                .await?
+            }
         };
 
+        // check download complete
+        match content_length {
+            Some(expected) if final_file_size != expected => {
+                return Err(DlmError::IncompleteDownload {
+                    expected,
+                    actual: final_file_size,
+                });
+            }
+            None => {
+                self.pb_manager.log_above_progress_bars(&format!(
+                    "No Content-Length available for {}, cannot verify download completeness",
+                    filename
+                ));
+            }
+            _ => {}
+        }
+
+        // verify before finalizing so a corrupt body stays a `.part`
+        if let Some(checksum) = checksum {
+            match hasher {
+                Some(hasher) => checksum.verify(hasher)?,
+                None => checksum.verify_file(&tmp_name).await?,
+            }
+        }
+
+        let message = self
+            .finalize_download(&tmp_name, final_file_path, final_file_size)
+            .await?;
+        reservation.keep();
+        Ok(message)
+    }
+
+    /// Move a completed `.part` to its final path. A file found at the
+    /// destination is handled by the conflict policy: with `skip` it must
+    /// have appeared in the meantime and is left alone, with `overwrite` the
+    /// rename replaces it atomically. With `--remote-time`, the file gets the
+    /// `Last-Modified` date of its version.
+    async fn finalize_download(
+        &self,
+        tmp_name: &Path,
+        mut final_file_path: PathBuf,
+        final_file_size: u64,
+    ) -> Result<String, DlmError> {
+        let mut renamed = None;
+        if tfs::metadata(&final_file_path).await.is_ok() {
+            match self.on_conflict {
+                ConflictPolicy::Skip => {
+                    let message = format!(
+                        "Can't finalize download because the file {} already exists",
+                        final_file_path.display()
+                    );
+                    return Err(DlmError::other(message));
+                }
+                ConflictPolicy::Overwrite => {}
+                ConflictPolicy::Rename => {
+                    let reservation = self.reservations.reserve_free(&final_file_path);
+                    final_file_path = reservation.path().to_path_buf();
+                    renamed = Some(reservation);
+                }
+                ConflictPolicy::Backup => {
+                    tfs::rename(&final_file_path, backup_path(&final_file_path)).await?;
+                }
+            }
+        }
+
+        // dated before the rename so the final file never shows the local time
+        if self.remote_time {
+            let last_modified = Sidecar::load(tmp_name).await.and_then(|s| s.last_modified);
+            if let Some(time) = last_modified.as_deref().and_then(parse_http_date) {
+                let file = tfs::OpenOptions::new().write(true).open(tmp_name).await?;
+                file.into_std().await.set_modified(time)?;
+            }
+        }
+
+        // rename part file to final, its sidecar is only needed by a later update
+        tfs::rename(tmp_name, &final_file_path).await?;
+        if self.update {
+            Sidecar::keep_for(tmp_name, &final_file_path).await?;
+        } else {
+            Sidecar::remove(tmp_name).await?;
+        }
+        if let Some(reservation) = renamed {
+            reservation.keep();
+        }
+        Ok(format!(
+            "Completed {} [{}]",
+            file_name_of(&final_file_path),
+            pretty_bytes_size(final_file_size)
+        ))
+    }
+
+    /// Download the body over a single connection into `tmp_name`, appending
+    /// to it when resuming. Returns the size of the resulting `.part`.
+    async fn download_stream(
+        &self,
+        source: &Source,
+        tmp_name: &Path,
+        resume_action: &ResumeAction,
+        pb_dl: &ProgressBar,
+        dl_limiter: Option<&RateLimiter>,
+        mut hasher: Option<&mut Hasher>,
+    ) -> Result<u64, DlmError> {
         // build and send the download request
-        let mut request = self.client.get(&file_link.url);
-        if let ResumeAction::Resume(range) = &resume_action {
-            request = request.header(RANGE, range);
+        let mut request = self.client.get(&source.url).headers(source.headers.clone());
+        if let ResumeAction::Resume { offset, if_range } = resume_action {
+            request = request.header(RANGE, format!("bytes={offset}-"));
+            // the server sends the whole body instead if the resource changed
+            if let Some(validator) = if_range {
+                request = request.header(IF_RANGE, validator);
+            }
         }
         let mut dl_response = request.send().await?;
         if !dl_response.status().is_success() {
@@ -228,6 +648,63 @@ impl<'a> DownloadContext<'a> {
             return Err(DlmError::ResponseStatusNotSuccess { status_code });
         }
 
+        // The `.part` is only opened once the response shows how its body
+        // fits: appending anything but the requested range would corrupt it.
+        let append = match resume_action {
+            ResumeAction::Resume { offset, .. } => {
+                if dl_response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                    check_content_range_start(dl_response.headers(), *offset)?;
+                    true
+                } else {
+                    self.pb_manager.log_above_progress_bars(&format!(
+                        "Server sent the whole file instead of a range for {}, restarting the download from scratch",
+                        source.url
+                    ));
+                    pb_dl.set_position(0);
+                    if let Some(mut sidecar) = Sidecar::load(tmp_name).await {
+                        sidecar.reset_validators(&parse_metadata_from(dl_response.headers()));
+                        sidecar.save(tmp_name).await?;
+                    }
+                    false
+                }
+            }
+            _ => false,
+        };
+
+        // create/open file.part
+        // no need for a BufWriter because the HTTP chunks are rather large
+        let mut file = if append {
+            // the digest covers the whole file, starting with the prefix
+            // downloaded by a previous attempt
+            if let Some(hasher) = hasher.as_deref_mut() {
+                hasher.update_from_file(tmp_name).await?;
+            }
+            tfs::OpenOptions::new()
+                .append(true)
+                .create(false)
+                .open(tmp_name)
+                .await?
+        } else {
+            // truncate any stale .part (AlreadyComplete handled by the caller)
+            tfs::File::create(tmp_name).await?
+        };
+
+        self.stream_body(&mut dl_response, &mut file, pb_dl, dl_limiter, hasher)
+            .await?;
+        Ok(file.metadata().await?.len())
+    }
+
+    /// Stream the response body chunk by chunk into `file`, advancing `pb_dl`,
+    /// feeding `hasher` and honouring the bandwidth caps, then flush and sync
+    /// it to disk.
+    async fn stream_body(
+        &self,
+        dl_response: &mut reqwest::Response,
+        file: &mut tfs::File,
+        pb_dl: &ProgressBar,
+        dl_limiter: Option<&RateLimiter>,
+        mut hasher: Option<&mut Hasher>,
+    ) -> Result<(), DlmError> {
         // Two distinct timeouts guard the body stream:
         // - `first_byte_timeout` bounds the wait for the server to *start*
         //   sending the body. Slow-to-respond servers (high time-to-first-byte,
@@ -264,30 +741,97 @@ impl<'a> DownloadContext<'a> {
                 first_chunk = false;
             }
             file.write_all(&chunk).await?;
+            if let Some(hasher) = hasher.as_deref_mut() {
+                hasher.update(&chunk);
+            }
             pb_dl.inc(chunk.len() as u64);
+            if let Some(limiter) = &self.bandwidth_limiter {
+                limiter.acquire(chunk.len() as u64).await;
+            }
+            if let Some(limiter) = dl_limiter {
+                limiter.acquire(chunk.len() as u64).await;
+            }
         }
         file.flush().await?; // flush buffer → OS
         file.sync_all().await?; // sync OS → disk
-        let final_file_size = file.metadata().await?.len();
+        Ok(())
+    }
 
-        // check download complete
-        match content_length {
-            Some(expected) if final_file_size != expected => {
-                return Err(DlmError::IncompleteDownload {
-                    expected,
-                    actual: final_file_size,
-                });
-            }
-            None => {
-                self.pb_manager.log_above_progress_bars(&format!(
-                    "No Content-Length available for {}, cannot verify download completeness",
-                    filename
-                ));
-            }
-            _ => {}
+    /// Fetch the body as `ranges.len()` concurrent ranged GETs, each streamed
+    /// into its own segment file next to `tmp_name`, then stitch them into
+    /// `tmp_name`. Segments left over by an interrupted run are resumed from
+    /// their current size. Returns the size of the merged `.part`.
+    async fn download_segments(
+        &self,
+        source: &Source,
+        tmp_name: &Path,
+        ranges: &[(u64, u64)],
+        pb_dl: &ProgressBar,
+        dl_limiter: Option<&RateLimiter>,
+    ) -> Result<u64, DlmError> {
+        let segments = ranges.iter().enumerate().map(|(index, &range)| {
+            let segment_file = segment_path(tmp_name, index);
+            self.download_segment(source, segment_file, range, pb_dl, dl_limiter)
+        });
+        try_join_all(segments).await?;
+        merge_segments(tmp_name, ranges.len()).await
+    }
+
+    /// Download the inclusive byte range `start..=end` into `segment_file`,
+    /// resuming from whatever the file already holds.
+    async fn download_segment(
+        &self,
+        source: &Source,
+        segment_file: PathBuf,
+        (start, end): (u64, u64),
+        pb_dl: &ProgressBar,
+        dl_limiter: Option<&RateLimiter>,
+    ) -> Result<(), DlmError> {
+        let expected = end - start + 1;
+        let existing = existing_segment_len(&segment_file, (start, end)).await;
+        pb_dl.inc(existing);
+        if existing == expected {
+            return Ok(());
         }
 
-        finalize_download(&tmp_name, &final_file_path, &filename, final_file_size).await
+        let mut file = if existing == 0 {
+            tfs::File::create(&segment_file).await?
+        } else {
+            tfs::OpenOptions::new()
+                .append(true)
+                .open(&segment_file)
+                .await?
+        };
+
+        let range = format!("bytes={}-{end}", start + existing);
+        let mut dl_response = self
+            .client
+            .get(&source.url)
+            .headers(source.headers.clone())
+            .header(RANGE, range)
+            .send()
+            .await?;
+        if !dl_response.status().is_success() {
+            let status_code = dl_response.status().as_u16();
+            return Err(DlmError::ResponseStatusNotSuccess { status_code });
+        }
+        // anything but a partial response means the range was not honoured,
+        // and appending it would corrupt the segment
+        if dl_response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(DlmError::other(format!(
+                "server ignored the range request for segment {}",
+                segment_file.display()
+            )));
+        }
+        check_content_range_start(dl_response.headers(), start + existing)?;
+        self.stream_body(&mut dl_response, &mut file, pb_dl, dl_limiter, None)
+            .await?;
+
+        let actual = file.metadata().await?.len();
+        if actual != expected {
+            return Err(DlmError::IncompleteDownload { expected, actual });
+        }
+        Ok(())
     }
 
     /// Resolve filename when the URL does not contain the extension (e.g. redirect).
@@ -295,6 +839,7 @@ impl<'a> DownloadContext<'a> {
     async fn resolve_filename(
         &self,
         file_link: &mut FileLink,
+        source: &Source,
         disposition_filename: Option<String>,
     ) -> Result<(), DlmError> {
         // try to get the file name from the Content-Disposition header
@@ -314,10 +859,7 @@ impl<'a> DownloadContext<'a> {
         }
 
         // check if it is maybe a redirect
-        match self
-            .compute_filename_from_location_header(&file_link.url)
-            .await?
-        {
+        match self.compute_filename_from_location_header(source).await? {
             None => {
                 let msg = format!("No extension found for {}", file_link.url);
                 self.pb_manager.log_above_progress_bars(&msg);
@@ -332,9 +874,14 @@ impl<'a> DownloadContext<'a> {
 
     async fn compute_filename_from_location_header(
         &self,
-        url: &str,
+        source: &Source,
     ) -> Result<Option<FileLink>, DlmError> {
-        let head_result = self.client_no_redirect.head(url).send().await?;
+        let head_result = self
+            .client_no_redirect
+            .head(&source.url)
+            .headers(source.headers.clone())
+            .send()
+            .await?;
         if head_result.status().is_redirection() {
             // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Location
             match location_value(head_result.headers()) {
@@ -350,14 +897,50 @@ impl<'a> DownloadContext<'a> {
     }
 }
 
+/// Bytes of the inclusive range `start..=end` already held by `segment_file`.
+async fn existing_segment_len(segment_file: &Path, (start, end): (u64, u64)) -> u64 {
+    match tfs::metadata(segment_file).await {
+        Ok(meta) if meta.len() <= end - start + 1 => meta.len(),
+        // missing, or larger than the range it should hold — start over
+        _ => 0,
+    }
+}
+
+/// Where a download is fetched from: a URL and the headers of its entry.
+struct Source {
+    url: String,
+    headers: HeaderMap,
+}
+
+/// Make sure a `206 Partial Content` response starts at the requested
+/// `offset`, so that its body can be appended to the bytes before it.
+fn check_content_range_start(headers: &HeaderMap, offset: u64) -> Result<(), DlmError> {
+    if content_range_start(headers) == Some(offset) {
+        return Ok(());
+    }
+    let content_range = headers
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("missing")
+        .to_string();
+    Err(DlmError::ContentRangeMismatch {
+        offset,
+        content_range,
+    })
+}
+
 /// What to do with a `.part` file when (re)starting a download.
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 enum ResumeAction {
     /// Download the whole body fresh, truncating any existing `.part`.
     Fresh,
     /// Resume an existing `.part` by requesting an open-ended range from the
-    /// given offset (e.g. `bytes=1024-`).
-    Resume(String),
+    /// given offset (e.g. `bytes=1024-`), conditioned by `If-Range` on the
+    /// validator of the resource the `.part` was started from, if known.
+    Resume {
+        offset: u64,
+        if_range: Option<String>,
+    },
     /// The `.part` already holds the complete body — finalize without a GET.
     AlreadyComplete,
 }
@@ -370,6 +953,7 @@ async fn compute_resume_action(
     content_length: Option<u64>,
     supports_range: bool,
     tmp_name: &Path,
+    stored_validator: Option<String>,
 ) -> Result<ResumeAction, DlmError> {
     if !tmp_name.exists() {
         if !supports_range {
@@ -408,7 +992,10 @@ async fn compute_resume_action(
                 // set the progress bar to the current size; the elapsed/ETA
                 // clock is (re)started from the first byte in `download`.
                 pb_dl.set_position(tmp_size);
-                Ok(ResumeAction::Resume(format!("bytes={tmp_size}-")))
+                Ok(ResumeAction::Resume {
+                    offset: tmp_size,
+                    if_range: stored_validator,
+                })
             }
         },
         // range supported but unknown total — can't tell where the body ends,
@@ -448,32 +1035,34 @@ async fn already_completed_message(
     ))
 }
 
-/// Move a completed `.part` to its final path, guarding against a file that
-/// appeared at the destination in the meantime.
-async fn finalize_download(
-    tmp_name: &Path,
-    final_file_path: &Path,
-    filename: &str,
-    final_file_size: u64,
-) -> Result<String, DlmError> {
-    // check if the destination already has a finished file
-    if tfs::metadata(final_file_path).await.is_ok() {
-        let message = format!(
-            "Can't finalize download because the file {} already exists",
-            final_file_path.display()
-        );
-        return Err(DlmError::other(message));
-    }
-
-    // rename part file to final
-    tfs::rename(tmp_name, final_file_path).await?;
+/// Build the "up to date, skipping" message for a destination file the
+/// server reports as unchanged.
+async fn up_to_date_message(final_file_path: &Path, filename: &str) -> Result<String, DlmError> {
+    let final_file_size = tfs::metadata(final_file_path).await?.len();
     Ok(format!(
-        "Completed {} [{}]",
-        filename,
+        "Skipping {filename} because it is up to date [{}]",
         pretty_bytes_size(final_file_size)
     ))
 }
 
+/// Last modification date of the remote file, or today when unknown.
+fn remote_date(metadata: &Metadata) -> Date {
+    let timestamp = metadata
+        .last_modified
+        .as_deref()
+        .and_then(parse_http_date)
+        .and_then(|time| Timestamp::try_from(time).ok())
+        .unwrap_or_else(Timestamp::now);
+    timestamp.to_zoned(TimeZone::UTC).date()
+}
+
+/// Name of the file at `path`, for messages.
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod compute_resume_action_tests {
     use super::*;
@@ -496,7 +1085,7 @@ mod compute_resume_action_tests {
         let pb = ProgressBar::hidden();
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name, None)
             .await
             .unwrap();
 
@@ -510,7 +1099,7 @@ mod compute_resume_action_tests {
         let pb = ProgressBar::hidden();
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), false, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), false, &tmp_name, None)
             .await
             .unwrap();
 
@@ -524,7 +1113,7 @@ mod compute_resume_action_tests {
         let pb = ProgressBar::hidden();
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name, None)
             .await
             .unwrap();
 
@@ -540,7 +1129,7 @@ mod compute_resume_action_tests {
         pb.set_position(150);
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name, None)
             .await
             .unwrap();
 
@@ -555,14 +1144,41 @@ mod compute_resume_action_tests {
         let pb = ProgressBar::hidden();
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name, None)
             .await
             .unwrap();
 
-        assert_eq!(action, ResumeAction::Resume("bytes=40-".to_owned()));
+        assert_eq!(
+            action,
+            ResumeAction::Resume {
+                offset: 40,
+                if_range: None
+            }
+        );
         assert_eq!(pb.position(), 40);
     }
 
+    #[tokio::test]
+    async fn resume_is_conditioned_on_stored_validator() {
+        let dir = tempdir().unwrap();
+        let tmp_name = make_part(dir.path(), 40);
+        let pb = ProgressBar::hidden();
+        let pbm = ProgressBarManager::hidden();
+        let validator = Some("\"v1\"".to_string());
+
+        let action = compute_resume_action(&pb, &pbm, Some(100), true, &tmp_name, validator)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            action,
+            ResumeAction::Resume {
+                offset: 40,
+                if_range: Some("\"v1\"".to_string())
+            }
+        );
+    }
+
     #[tokio::test]
     async fn part_without_range_support_is_overridden() {
         let dir = tempdir().unwrap();
@@ -571,7 +1187,7 @@ mod compute_resume_action_tests {
         pb.set_position(40);
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, Some(100), false, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, Some(100), false, &tmp_name, None)
             .await
             .unwrap();
 
@@ -587,7 +1203,7 @@ mod compute_resume_action_tests {
         pb.set_position(40);
         let pbm = ProgressBarManager::hidden();
 
-        let action = compute_resume_action(&pb, &pbm, None, true, &tmp_name)
+        let action = compute_resume_action(&pb, &pbm, None, true, &tmp_name, None)
             .await
             .unwrap();
 
@@ -1,5 +1,6 @@
 use crate::dlm_error::DlmError;
 use percent_encoding::percent_decode_str;
+use std::path::{Component, Path, PathBuf};
 
 /// Base names Windows reserves for legacy device files; we suffix `_` to dodge
 /// them. Matched case-insensitively.
@@ -57,9 +58,25 @@ pub fn cleanup_filename(input: &str) -> String {
     result
 }
 
+/// `dir` made safe to join to the output directory: only its plain
+/// components are kept, each sanitized via `cleanup_filename`, so neither
+/// an absolute path nor `..` can lead outside of it.
+pub fn sanitize_relative_dir(dir: &Path) -> PathBuf {
+    dir.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(cleanup_filename(&name.to_string_lossy())),
+            _ => None,
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct FileLink {
     pub url: String,
+    pub host: String,
+    /// Sanitized directories of the URL path, without the file name.
+    pub directories: Vec<String>,
     pub filename_without_extension: String,
     pub extension: Option<String>,
 }
@@ -77,6 +94,9 @@ impl FileLink {
             DlmError::other(format!("FileLink could not parse URL '{trimmed}': {e}"))
         })?;
 
+        // lowercased by the parser; empty for host-less schemes like `file:`
+        let host = parsed.host_str().unwrap_or_default().to_string();
+
         // `path_segments` keeps segments percent-encoded; we decode after the
         // split so a literal '%2F' inside a segment is not mistaken for a
         // path separator. Query and fragment are already separated by the
@@ -86,6 +106,20 @@ impl FileLink {
             .and_then(|mut s| s.next_back())
             .filter(|s| !s.is_empty())
             .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+        // Same decoding as the file name. Segments like `..` or `.` become
+        // empty once cleaned and are dropped, so they can't escape a directory.
+        let directories = parsed
+            .path_segments()
+            .map(|segments| {
+                let mut segments: Vec<_> = segments.collect();
+                segments.pop();
+                segments
+                    .into_iter()
+                    .map(|s| cleanup_filename(&percent_decode_str(s).decode_utf8_lossy()))
+                    .filter(|s| !s.is_empty() && s != "." && s != "..")
+                    .collect()
+            })
+            .unwrap_or_default();
         let query = parsed.query().filter(|q| !q.is_empty());
         // When the path yields a name with an extension we drop the query —
         // it's noise that doesn't help disambiguate. When the path has no
@@ -117,11 +151,42 @@ impl FileLink {
 
         Ok(Self {
             url: url.to_string(),
+            host,
+            directories,
             filename_without_extension,
             extension,
         })
     }
 
+    /// Save under `filename` instead of the name derived from the URL. Only
+    /// its last path component is kept.
+    pub fn set_filename(&mut self, filename: &str) -> Result<(), DlmError> {
+        let safe_name = Path::new(filename)
+            .file_name()
+            .map(|name| cleanup_filename(&name.to_string_lossy()))
+            .unwrap_or_default();
+        if safe_name.is_empty() {
+            return Err(DlmError::other(format!(
+                "'{filename}' is not a usable filename"
+            )));
+        }
+        let (extension, filename_without_extension) =
+            Self::extract_extension_from_filename(&safe_name);
+        self.extension = extension;
+        self.filename_without_extension = filename_without_extension;
+        Ok(())
+    }
+
+    /// Relative directory mirroring the URL, e.g. `host/a/b` for
+    /// `https://host/a/b/file.tar`.
+    pub fn url_directory(&self) -> PathBuf {
+        let host = cleanup_filename(&self.host);
+        std::iter::once(host.as_str())
+            .chain(self.directories.iter().map(String::as_str))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
     pub fn filename(&self) -> String {
         let joined = match &self.extension {
             Some(ext) => format!("{}.{ext}", self.filename_without_extension),
@@ -171,6 +236,12 @@ mod file_link_tests {
         }
     }
 
+    #[test]
+    fn host_is_extracted_lowercased() {
+        let fl = FileLink::new("https://Downloads.Example.COM:8443/a/file.bin").unwrap();
+        assert_eq!(fl.host, "downloads.example.com");
+    }
+
     #[test]
     fn trailing_slash() {
         let url = "https://www.google.com/area51/";
@@ -335,6 +406,17 @@ mod file_link_tests {
         );
     }
 
+    #[test]
+    fn sanitized_dir_stays_relative() {
+        let dir = |dir: &str| sanitize_relative_dir(Path::new(dir));
+        assert_eq!(dir("sub/dir"), PathBuf::from("sub/dir"));
+        assert_eq!(dir("../x"), PathBuf::from("x"));
+        assert_eq!(dir("/abs/./path"), PathBuf::from("abs/path"));
+        assert_eq!(dir("a/../../b"), PathBuf::from("a/b"));
+        assert_eq!(dir("a/ .. /CON"), PathBuf::from("a/CON_"));
+        assert_eq!(dir(".."), PathBuf::new());
+    }
+
     #[test]
     fn cleanup_replaces_control_chars() {
         assert_eq!(cleanup_filename("a\u{0}b\u{1}c\u{1f}d.txt"), "a_b_c_d.txt");
@@ -415,4 +497,49 @@ mod file_link_tests {
             other => panic!("expected Other error, got {other:?}"),
         }
     }
+
+    #[test]
+    fn set_filename_overrides_url_name() {
+        let mut fl = FileLink::new("https://example.com/download?id=42").unwrap();
+        fl.set_filename("archive.tar.gz").unwrap();
+        assert_eq!(fl.filename(), "archive.tar.gz");
+        assert_eq!(fl.extension, Some("gz".to_string()));
+    }
+
+    #[test]
+    fn set_filename_drops_directories() {
+        let mut fl = FileLink::new("https://example.com/file.bin").unwrap();
+        fl.set_filename("../../etc/passwd").unwrap();
+        assert_eq!(fl.filename(), "passwd");
+        assert!(fl.set_filename("..").is_err());
+    }
+
+    #[test]
+    fn url_directory_mirrors_host_and_path() {
+        let fl = FileLink::new("https://Host.com/a/b/file.tar").unwrap();
+        assert_eq!(fl.directories, vec!["a", "b"]);
+        assert_eq!(fl.url_directory(), PathBuf::from("host.com/a/b"));
+    }
+
+    #[test]
+    fn url_directory_without_path() {
+        let fl = FileLink::new("https://host.com/file.tar").unwrap();
+        assert_eq!(fl.url_directory(), PathBuf::from("host.com"));
+    }
+
+    #[test]
+    fn url_directory_drops_traversal_segments() {
+        // dot segments, even encoded, are resolved by the URL parser
+        let fl = FileLink::new("https://host.com/a/%2E%2E/%2E%2E/b%2Fc/file.tar").unwrap();
+        assert_eq!(fl.directories, vec!["b_c"]);
+        // an encoded separator can't smuggle one in either
+        let fl = FileLink::new("https://host.com/..%2F..%2Fetc/file.tar").unwrap();
+        assert_eq!(fl.directories, vec![".._.._etc"]);
+    }
+
+    #[test]
+    fn url_directory_sanitizes_segments() {
+        let fl = FileLink::new("https://host.com/con/a%3Ab/file.tar").unwrap();
+        assert_eq!(fl.directories, vec!["con_", "a_b"]);
+    }
 }
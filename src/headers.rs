@@ -1,8 +1,12 @@
+use jiff::Timestamp;
+use jiff::fmt::rfc2822::{DateTimeParser, DateTimePrinter};
 use percent_encoding::percent_decode_str;
 use reqwest::header::{
-    ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, HeaderMap, LOCATION,
+    ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, ETAG, HeaderMap,
+    LAST_MODIFIED, LOCATION,
 };
 use std::path::Path;
+use std::time::SystemTime;
 
 use crate::file_link::cleanup_filename;
 
@@ -15,6 +19,16 @@ pub fn content_range_total_size(headers: &HeaderMap) -> Option<u64> {
         .and_then(|(_, total)| total.parse().ok())
 }
 
+/// Extract the first byte position from Content-Range header: `bytes 100-199/200` → 100
+pub fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes "))
+        .and_then(|v| v.split_once('-'))
+        .and_then(|(start, _)| start.trim().parse().ok())
+}
+
 pub fn content_length_value(headers: &HeaderMap) -> Option<u64> {
     headers
         .get(CONTENT_LENGTH)
@@ -39,14 +53,75 @@ pub fn location_value(headers: &HeaderMap) -> Option<&str> {
     headers.get(LOCATION).and_then(|v| v.to_str().ok())
 }
 
-/// Pull `(content-length, supports range, disposition filename)` out of a
-/// response's headers. Prefers `Content-Range` total over `Content-Length`
-/// because a `Range: bytes=0-0` probe makes `Content-Length` equal to 1.
-pub fn parse_metadata_from(headers: &HeaderMap) -> (Option<u64>, bool, Option<String>) {
-    let cl = content_range_total_size(headers).or_else(|| content_length_value(headers));
-    let sr = supports_range_bytes(headers);
-    let df = content_disposition_value(headers).and_then(parse_filename_header);
-    (cl, sr, df)
+pub fn etag_value(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string)
+}
+
+pub fn last_modified_value(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string)
+}
+
+/// What the server tells about a resource before its body is downloaded.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub content_length: Option<u64>,
+    pub supports_range: bool,
+    /// Filename from the Content-Disposition header.
+    pub disposition_filename: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Metadata {
+    /// Validator identifying this version of the resource, usable in an
+    /// `If-Range` header: the ETag if it is strong, the Last-Modified date
+    /// otherwise. Weak ETags (`W/"..."`) never match in `If-Range`.
+    pub fn validator(&self) -> Option<&str> {
+        if_range_validator(self.etag.as_deref(), self.last_modified.as_deref())
+    }
+}
+
+/// Pick the validator to send in `If-Range`: `etag` if it is strong,
+/// `last_modified` otherwise.
+pub fn if_range_validator<'a>(
+    etag: Option<&'a str>,
+    last_modified: Option<&'a str>,
+) -> Option<&'a str> {
+    etag.filter(|etag| !etag.starts_with("W/"))
+        .or(last_modified)
+}
+
+/// Pull the `Metadata` out of a response's headers. Prefers `Content-Range`
+/// total over `Content-Length` because a `Range: bytes=0-0` probe makes
+/// `Content-Length` equal to 1.
+pub fn parse_metadata_from(headers: &HeaderMap) -> Metadata {
+    Metadata {
+        content_length: content_range_total_size(headers).or_else(|| content_length_value(headers)),
+        supports_range: supports_range_bytes(headers),
+        disposition_filename: content_disposition_value(headers).and_then(parse_filename_header),
+        etag: etag_value(headers),
+        last_modified: last_modified_value(headers),
+    }
+}
+
+/// Format `time` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(time: SystemTime) -> Option<String> {
+    let timestamp = Timestamp::try_from(time).ok()?;
+    DateTimePrinter::new()
+        .timestamp_to_rfc9110_string(&timestamp)
+        .ok()
+}
+
+/// Parse an HTTP date such as a `Last-Modified` value.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let timestamp = DateTimeParser::new().parse_timestamp(value).ok()?;
+    Some(SystemTime::from(timestamp))
 }
 
 pub fn parse_filename_header(content_disposition: &str) -> Option<String> {
@@ -118,6 +193,61 @@ mod headers_tests {
         h
     }
 
+    #[test]
+    fn content_range_start_ok() {
+        let h = headers_with(CONTENT_RANGE, "bytes 100-199/200");
+        assert_eq!(content_range_start(&h), Some(100));
+        let h = headers_with(CONTENT_RANGE, "bytes 0-99/*");
+        assert_eq!(content_range_start(&h), Some(0));
+    }
+
+    #[test]
+    fn content_range_start_invalid_or_missing() {
+        let h = headers_with(CONTENT_RANGE, "bytes */200");
+        assert_eq!(content_range_start(&h), None);
+        let h = headers_with(CONTENT_RANGE, "items 1-2/3");
+        assert_eq!(content_range_start(&h), None);
+        assert_eq!(content_range_start(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn parse_metadata_from_probe() {
+        let mut h = headers_with(CONTENT_RANGE, "bytes 0-0/12345");
+        h.insert(CONTENT_LENGTH, HeaderValue::from_static("1"));
+        h.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        h.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let metadata = parse_metadata_from(&h);
+        assert_eq!(metadata.content_length, Some(12345));
+        assert!(metadata.supports_range);
+        assert_eq!(metadata.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(metadata.last_modified, None);
+    }
+
+    #[test]
+    fn validator_prefers_strong_etag() {
+        let metadata = Metadata {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            ..Metadata::default()
+        };
+        assert_eq!(metadata.validator(), Some("\"abc\""));
+    }
+
+    #[test]
+    fn validator_skips_weak_etag() {
+        let metadata = Metadata {
+            etag: Some("W/\"abc\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            ..Metadata::default()
+        };
+        assert_eq!(metadata.validator(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        let weak_only = Metadata {
+            etag: Some("W/\"abc\"".to_string()),
+            ..Metadata::default()
+        };
+        assert_eq!(weak_only.validator(), None);
+    }
+
     #[test]
     fn supports_range_bytes_mixed_case() {
         let h = headers_with(ACCEPT_RANGES, "Bytes");
@@ -247,4 +377,20 @@ mod headers_tests {
     fn percent_decode_invalid_hex() {
         assert_eq!(percent_decode_filename("file%GG"), "file%GG");
     }
+
+    #[test]
+    fn http_date_format() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(784_111_777);
+        assert_eq!(
+            http_date(time),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_http_date_round_trips() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(784_111_777);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
 }
@@ -0,0 +1,461 @@
+use crate::client::{ClientConfig, make_client};
+use crate::dlm_error::DlmError;
+use crate::download_entry::DownloadEntry;
+use crate::file_link::cleanup_filename;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use reqwest::{Client, Url};
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use tokio::fs as tfs;
+
+/// Which links of a page are downloaded.
+#[derive(Debug, Default)]
+pub struct LinkFilter {
+    /// Regular expression the whole URL must match.
+    pub regex: Option<Regex>,
+    /// Lowercase extensions without leading dot, e.g. `iso` or `tar.gz`,
+    /// any if empty.
+    pub extensions: Vec<String>,
+    /// Only links on the host of the page.
+    pub same_host: bool,
+}
+
+impl LinkFilter {
+    fn accepts(&self, link: &Url, page: Option<&Url>) -> bool {
+        if self.same_host && page.is_none_or(|page| page.host_str() != link.host_str()) {
+            return false;
+        }
+        if !self.extensions.is_empty() {
+            let name = link
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let has_extension = |extension: &String| {
+                name.strip_suffix(extension.as_str())
+                    .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+            };
+            if !self.extensions.iter().any(has_extension) {
+                return false;
+            }
+        }
+        self.regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(link.as_str()))
+    }
+}
+
+/// Entries for the links of the HTML page at `source`, an `http(s)://` URL
+/// or a local file, kept by `filter` and without duplicates.
+pub async fn load(
+    source: &str,
+    client_config: &ClientConfig<'_>,
+    filter: &LinkFilter,
+) -> Result<Vec<DownloadEntry>, DlmError> {
+    let (html, page_url) = if source.starts_with("http://") || source.starts_with("https://") {
+        let client = make_client(client_config, true)?;
+        let (html, page_url) = fetch_page(&client, source).await?;
+        (html, Some(page_url))
+    } else {
+        (tfs::read_to_string(source).await?, None)
+    };
+
+    let (base, links) = extract_links(&html, page_url.as_ref());
+    if filter.same_host && base.is_none() {
+        return Err(DlmError::other(format!(
+            "'--same-host' requires a page URL or a <base href> in {source}"
+        )));
+    }
+    let mut seen = HashSet::new();
+    let entries: Vec<DownloadEntry> = links
+        .into_iter()
+        .filter(|link| filter.accepts(link, base.as_ref()))
+        .filter(|link| seen.insert(link.to_string()))
+        .zip(1..)
+        .map(|(link, index)| DownloadEntry {
+            index,
+            ..DownloadEntry::new(link.as_str())
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err(DlmError::other(format!("no link to download in {source}")));
+    }
+    Ok(entries)
+}
+
+/// Entries for the files of the directory listing at `page`, e.g. an Apache
+/// or nginx autoindex, and of its subdirectories down to `max_depth` levels,
+/// kept by `filter`. Only the links under the directory of `page` are
+/// followed, and each file is saved under its path relative to it. Errors
+/// are the messages to log for the subdirectories that could not be listed.
+pub async fn crawl(
+    page: &str,
+    client_config: &ClientConfig<'_>,
+    filter: &LinkFilter,
+    max_depth: u32,
+) -> Result<Vec<Result<DownloadEntry, String>>, DlmError> {
+    let client = make_client(client_config, true)?;
+    let (html, page_url) = fetch_page(&client, page).await?;
+    let root = directory_of(&page_url);
+
+    let mut listings = VecDeque::from([(page_url, html, 0)]);
+    let mut visited = HashSet::from([root.clone()]);
+    let mut files = HashSet::new();
+    let mut entries = Vec::new();
+    while let Some((listing_url, html, depth)) = listings.pop_front() {
+        let (_, links) = extract_links(&html, Some(&listing_url));
+        for mut link in links {
+            let Some(dir) = relative_dir(&root, &link) else {
+                continue;
+            };
+            if link.path().ends_with('/') {
+                // the sorting links of a listing only differ by their query
+                link.set_query(None);
+                if depth < max_depth && visited.insert(link.clone()) {
+                    match fetch_page(&client, link.as_str()).await {
+                        Ok((html, url)) => listings.push_back((url, html, depth + 1)),
+                        Err(e) => entries.push(Err(format!("Error for {link}: {e}"))),
+                    }
+                }
+            } else if filter.accepts(&link, Some(&root)) && files.insert(link.clone()) {
+                entries.push(Ok(DownloadEntry {
+                    dir: (dir != PathBuf::new()).then_some(dir),
+                    index: files.len(),
+                    ..DownloadEntry::new(link.as_str())
+                }));
+            }
+        }
+    }
+    if files.is_empty() {
+        return Err(DlmError::other(format!("no file to download under {root}")));
+    }
+    Ok(entries)
+}
+
+/// Body of the page at `url` and its URL after redirects, against which
+/// relative links are resolved.
+async fn fetch_page(client: &Client, url: &str) -> Result<(String, Url), DlmError> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(DlmError::ResponseStatusNotSuccess {
+            status_code: response.status().as_u16(),
+        });
+    }
+    let page_url = response.url().clone();
+    Ok((response.text().await?, page_url))
+}
+
+/// `url` up to the last `/` of its path.
+fn directory_of(url: &Url) -> Url {
+    let mut directory = url.clone();
+    directory.set_query(None);
+    directory.set_fragment(None);
+    let path = url.path();
+    directory.set_path(&path[..=path.rfind('/').unwrap_or(0)]);
+    directory
+}
+
+/// Directories of the path of `link` below `root`, decoded and sanitized
+/// like file names. `None` if `link` is not under `root`.
+fn relative_dir(root: &Url, link: &Url) -> Option<PathBuf> {
+    if link.origin() != root.origin() {
+        return None;
+    }
+    let mut segments: Vec<&str> = link.path().strip_prefix(root.path())?.split('/').collect();
+    // the file name, or nothing for a directory
+    segments.pop();
+    Some(
+        segments
+            .into_iter()
+            .map(|segment| cleanup_filename(&percent_decode_str(segment).decode_utf8_lossy()))
+            .filter(|segment| !segment.is_empty() && segment != "." && segment != "..")
+            .collect(),
+    )
+}
+
+/// The `href` and `src` links of `html` that resolve to `http(s)` URLs,
+/// without fragment, in document order, together with the base URL they
+/// were resolved against: `page_url` unless the page sets a `<base href>`.
+pub fn extract_links(html: &str, page_url: Option<&Url>) -> (Option<Url>, Vec<Url>) {
+    let mut base = page_url.cloned();
+    let mut links = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or_default();
+
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .filter(|end| *end > 0)
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        for (attribute, value) in attributes(&tag[name_end..]) {
+            let value = value.trim();
+            if value.is_empty() || value.starts_with('#') {
+                continue;
+            }
+            match attribute.as_str() {
+                "href" if name == "base" => base = resolve(base.as_ref(), value).or(base),
+                "href" | "src" => links.extend(resolve(base.as_ref(), value)),
+                _ => {}
+            }
+        }
+        // the content of scripts and styles is not markup
+        if name == "script" || name == "style" {
+            let close = format!("</{name}");
+            rest = find_ignore_ascii_case(rest, &close).map_or("", |end| &rest[end..]);
+        }
+    }
+    (base, links)
+}
+
+/// Index of the first occurrence of the ASCII `needle` in `haystack`,
+/// ignoring ASCII case.
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn resolve(base: Option<&Url>, value: &str) -> Option<Url> {
+    let mut url = match base {
+        Some(base) => base.join(value),
+        None => Url::parse(value),
+    }
+    .ok()?;
+    url.set_fragment(None);
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+/// Index of the `>` closing the tag starting `tag`, outside quoted values.
+fn tag_end(tag: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '>') => return index,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    tag.len()
+}
+
+/// The attributes of a tag, names lowercased and values decoded.
+fn attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return attributes;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=').map(str::trim_start) {
+            Some(quoted) if quoted.starts_with(['"', '\'']) => {
+                let quote = &quoted[..1];
+                let value = &quoted[1..];
+                let end = value.find(quote).unwrap_or(value.len());
+                rest = value.get(end + 1..).unwrap_or_default();
+                &value[..end]
+            }
+            Some(unquoted) => {
+                let end = unquoted.find(char::is_whitespace).unwrap_or(unquoted.len());
+                rest = &unquoted[end..];
+                &unquoted[..end]
+            }
+            None => "",
+        };
+        attributes.push((name, decode_entities(value)));
+    }
+}
+
+/// Decode the character references of an attribute value, e.g. `&amp;`.
+/// Unknown ones are kept as is.
+fn decode_entities(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 8)
+            .map(|end| &rest[1..=end]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod html_links_tests {
+    use super::*;
+
+    fn links(html: &str, page: &str) -> Vec<String> {
+        let page = Url::parse(page).unwrap();
+        extract_links(html, Some(&page))
+            .1
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn extracts_href_and_src_in_order() {
+        let html = r#"<html><body>
+            <a href="a.iso">A</a>
+            <IMG SRC='/img/b.png' alt="b">
+            <a class=x href=../c.zip>C</a>
+            <a href="https://other.example.com/d.bin#part">D</a>
+            </body></html>"#;
+        assert_eq!(
+            links(html, "https://example.com/files/index.html"),
+            [
+                "https://example.com/files/a.iso",
+                "https://example.com/img/b.png",
+                "https://example.com/c.zip",
+                "https://other.example.com/d.bin",
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_non_http_links_comments_and_scripts() {
+        let html = r##"
+            <a href="mailto:me@example.com">mail</a>
+            <a href="javascript:void(0)">js</a>
+            <a href="#top">top</a>
+            <!-- <a href="commented.bin"> -->
+            <script src="app.js">var s = '<a href="in-script.bin">';</script>
+            <a title="x > y" href="kept.bin">kept</a>
+        "##;
+        assert_eq!(
+            links(html, "https://example.com/"),
+            ["https://example.com/app.js", "https://example.com/kept.bin"]
+        );
+    }
+
+    #[test]
+    fn script_and_style_end_tags_ignore_case() {
+        let html = r#"
+            <STYLE>a[href="in-style.bin"] {}</Style>
+            <a href="first.bin">first</a>
+            <Script>document.write('<a href="in-script.bin">')</SCRIPT>
+            <a href="second.bin">second</a>
+        "#;
+        assert_eq!(
+            links(html, "https://example.com/"),
+            [
+                "https://example.com/first.bin",
+                "https://example.com/second.bin"
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_entities() {
+        let html = r#"<a href="get?id=1&amp;name=a&#x2B;b&#46;bin&unknown;">x</a>"#;
+        assert_eq!(
+            links(html, "https://example.com/"),
+            ["https://example.com/get?id=1&name=a+b.bin&unknown;"]
+        );
+    }
+
+    #[test]
+    fn base_href_changes_the_base() {
+        let html = r#"<head><base href="https://cdn.example.com/v2/"></head><a href="a.iso">"#;
+        let (base, links) = extract_links(html, None);
+        assert_eq!(base.unwrap().as_str(), "https://cdn.example.com/v2/");
+        assert_eq!(links[0].as_str(), "https://cdn.example.com/v2/a.iso");
+    }
+
+    #[test]
+    fn relative_links_need_a_base() {
+        let html = r#"<a href="a.iso"></a><a href="https://example.com/b.iso"></a>"#;
+        let (base, links) = extract_links(html, None);
+        assert!(base.is_none());
+        assert_eq!(links, [Url::parse("https://example.com/b.iso").unwrap()]);
+    }
+
+    #[test]
+    fn directory_of_strips_the_file_name() {
+        let url = Url::parse("https://example.com/pub/index.html?C=N").unwrap();
+        assert_eq!(directory_of(&url).as_str(), "https://example.com/pub/");
+        let url = Url::parse("https://example.com/pub/").unwrap();
+        assert_eq!(directory_of(&url).as_str(), "https://example.com/pub/");
+    }
+
+    #[test]
+    fn relative_dir_stays_under_the_root() {
+        let root = Url::parse("https://example.com/pub/").unwrap();
+        let dir = |link: &str| relative_dir(&root, &Url::parse(link).unwrap());
+        assert_eq!(dir("https://example.com/pub/a.iso"), Some(PathBuf::new()));
+        assert_eq!(
+            dir("https://example.com/pub/v1/my%20dir/a.iso"),
+            Some(PathBuf::from("v1/my dir"))
+        );
+        assert_eq!(
+            dir("https://example.com/pub/v1/"),
+            Some(PathBuf::from("v1"))
+        );
+        assert_eq!(dir("https://example.com/"), None);
+        assert_eq!(dir("https://example.com/public/a.iso"), None);
+        assert_eq!(dir("http://example.com/pub/a.iso"), None);
+        assert_eq!(dir("https://other.com/pub/a.iso"), None);
+    }
+
+    #[test]
+    fn filter_by_extension_host_and_regex() {
+        let page = Url::parse("https://example.com/").unwrap();
+        let url = |s: &str| Url::parse(s).unwrap();
+        let filter = LinkFilter {
+            regex: Some(Regex::new("release").unwrap()),
+            extensions: vec!["iso".to_string(), "tar.gz".to_string()],
+            same_host: true,
+        };
+        assert!(filter.accepts(&url("https://example.com/release.tar.gz"), Some(&page)));
+        assert!(!filter.accepts(&url("https://example.com/release/iso"), Some(&page)));
+        assert!(!filter.accepts(&url("https://example.com/.iso?release"), Some(&page)));
+        assert!(filter.accepts(&url("https://example.com/release-1.ISO"), Some(&page)));
+        assert!(!filter.accepts(&url("https://example.com/release-1.zip"), Some(&page)));
+        assert!(!filter.accepts(&url("https://example.com/beta-1.iso"), Some(&page)));
+        assert!(!filter.accepts(&url("https://other.com/release-1.iso"), Some(&page)));
+        assert!(LinkFilter::default().accepts(&url("https://other.com/"), None));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::args::parse_header;
+use crate::checksum::Checksum;
+use crate::dlm_error::DlmError;
+use crate::download_entry::{DownloadEntry, is_empty_line};
+use crate::file_link::sanitize_relative_dir;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Priority of the entries that do not set one: they come last.
+const LOWEST_PRIORITY: u32 = u32::MAX;
+
+/// One download of a JSON input, e.g.
+/// `{"url": "https://host/file.iso", "filename": "debian.iso", "size": 658505728}`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonEntry {
+    url: String,
+    filename: Option<String>,
+    dir: Option<PathBuf>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// e.g. `sha256=9f86d0...`
+    checksum: Option<String>,
+    #[serde(default)]
+    mirrors: Vec<String>,
+    /// Order of the downloads, the lowest first as in Metalink.
+    priority: Option<u32>,
+    size: Option<u64>,
+}
+
+impl JsonEntry {
+    fn into_entry(self) -> Result<DownloadEntry, DlmError> {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| parse_header(&format!("{name}: {value}")))
+            .collect::<Result<_, _>>()?;
+        Ok(DownloadEntry {
+            mirrors: self.mirrors,
+            filename: self.filename,
+            dir: self.dir.as_deref().map(sanitize_relative_dir),
+            headers,
+            size: self.size,
+            checksum: self.checksum.as_deref().map(Checksum::parse).transpose()?,
+            priority: self.priority,
+            ..DownloadEntry::new(&self.url)
+        })
+    }
+}
+
+/// Whether an input file holds JSON rather than lines of URLs, judging by
+/// its first line that is neither blank nor a comment.
+pub fn is_json_file(path: &Path) -> Result<bool, DlmError> {
+    let reader = BufReader::new(File::open(path)?);
+    for line in reader.lines() {
+        let line = line?;
+        if !is_empty_line(&line) {
+            return Ok(line.trim_start().starts_with(['[', '{']));
+        }
+    }
+    Ok(false)
+}
+
+/// Parse a single JSON object into an entry. This is how every line of
+/// JSON Lines is read, from a JSON input file or among URL lines.
+pub fn parse_entry(object: &str) -> Result<DownloadEntry, DlmError> {
+    let entry: JsonEntry = serde_json::from_str(object).map_err(invalid_entry)?;
+    entry.into_entry()
+}
+
+fn invalid_entry(e: serde_json::Error) -> DlmError {
+    DlmError::other(format!("invalid JSON entry - {e}"))
+}
+
+/// Parse a JSON array of entries or JSON Lines, one object per line, into
+/// entries ordered by priority. Blank lines and `#` comments of JSON Lines
+/// are skipped. Errors are the messages to log for the objects that could
+/// not be turned into an entry; a malformed array fails as a whole.
+pub fn parse(content: &str) -> Result<Vec<Result<DownloadEntry, String>>, DlmError> {
+    let mut entries: Vec<Result<DownloadEntry, String>> = if content.trim_start().starts_with('[') {
+        let values: Vec<serde_json::Value> = serde_json::from_str(content)
+            .map_err(|e| DlmError::other(format!("invalid JSON input - {e}")))?;
+        values
+            .into_iter()
+            .zip(1..)
+            .map(|(value, index)| {
+                serde_json::from_value::<JsonEntry>(value)
+                    .map_err(invalid_entry)
+                    .and_then(JsonEntry::into_entry)
+                    .map(|entry| DownloadEntry { index, ..entry })
+                    .map_err(|e| format!("Error for entry {index}: {e}"))
+            })
+            .collect()
+    } else {
+        content
+            .lines()
+            .zip(1..)
+            .filter(|(line, _)| !is_empty_line(line))
+            .map(|(line, index)| {
+                parse_entry(line)
+                    .map(|entry| DownloadEntry { index, ..entry })
+                    .map_err(|e| format!("Error for {}: {e}", line.trim()))
+            })
+            .collect()
+    };
+    // stable: entries sharing a priority keep the input order, the ones that
+    // could not be parsed come last
+    entries.sort_by_key(|entry| {
+        entry
+            .as_ref()
+            .ok()
+            .and_then(|entry| entry.priority)
+            .unwrap_or(LOWEST_PRIORITY)
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod json_input_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const DIGEST: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn detects_json_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("links");
+        for (content, expected) in [
+            ("\n  [{\"url\": \"https://example.com/a\"}]", true),
+            ("{\"url\": \"https://example.com/a\"}\n", true),
+            ("# urls\n{\"url\": \"https://example.com/a\"}\n", true),
+            ("https://example.com/a\n", false),
+            ("", false),
+        ] {
+            std::fs::write(&path, content).unwrap();
+            assert_eq!(is_json_file(&path).unwrap(), expected, "{content}");
+        }
+    }
+
+    #[test]
+    fn maps_every_field() {
+        let object = format!(
+            r#"{{"url": "https://example.com/a.iso", "filename": "b.iso", "dir": "isos",
+                "headers": {{"X-Token": "abc"}}, "checksum": "sha256={DIGEST}",
+                "mirrors": ["https://mirror.example.com/a.iso"], "priority": 2, "size": 42}}"#
+        );
+        let entry = parse_entry(&object).unwrap();
+        assert_eq!(entry.url, "https://example.com/a.iso");
+        assert_eq!(entry.filename.as_deref(), Some("b.iso"));
+        assert_eq!(entry.dir, Some(PathBuf::from("isos")));
+        assert_eq!(
+            entry.headers,
+            vec![("X-Token".to_string(), "abc".to_string())]
+        );
+        assert_eq!(entry.checksum.unwrap().expected, DIGEST);
+        assert_eq!(entry.mirrors, vec!["https://mirror.example.com/a.iso"]);
+        assert_eq!(entry.size, Some(42));
+        assert_eq!(entry.priority, Some(2));
+    }
+
+    #[test]
+    fn dir_stays_relative() {
+        let entry = parse_entry(r#"{"url": "https://example.com/a", "dir": "/tmp/../x"}"#).unwrap();
+        assert_eq!(entry.dir, Some(PathBuf::from("tmp/x")));
+        let entry = parse_entry(r#"{"url": "https://example.com/a", "dir": "../x"}"#).unwrap();
+        assert_eq!(entry.dir, Some(PathBuf::from("x")));
+    }
+
+    #[test]
+    fn unknown_field_is_an_error() {
+        let error = parse_entry(r#"{"url": "https://example.com/a", "out": "b"}"#).unwrap_err();
+        assert!(error.to_string().contains("unknown field `out`"), "{error}");
+        assert!(parse_entry(r#"{"filename": "b"}"#).is_err());
+    }
+
+    #[test]
+    fn array_is_ordered_by_priority() {
+        let entries = parse(
+            r#"[
+                {"url": "https://example.com/last"},
+                {"url": "https://example.com/second", "priority": 5},
+                {"url": "https://example.com/first", "priority": 1}
+            ]"#,
+        )
+        .unwrap();
+        let urls: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.as_ref().unwrap().url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/first",
+                "https://example.com/second",
+                "https://example.com/last"
+            ]
+        );
+        assert_eq!(entries[0].as_ref().unwrap().index, 3);
+    }
+
+    #[test]
+    fn invalid_line_fails_its_entry_only() {
+        let entries = parse(
+            "{\"url\": \"https://example.com/a\"}\n\n# comment\n{\"url\": 3}\n{\"url\": \"https://example.com/b\", \"checksum\": \"sha1=abc\"}\n",
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].as_ref().unwrap().index, 1);
+        assert!(
+            entries[1]
+                .as_ref()
+                .unwrap_err()
+                .contains("invalid JSON entry")
+        );
+        assert!(
+            entries[2]
+                .as_ref()
+                .unwrap_err()
+                .starts_with(r#"Error for {"url": "https://example.com/b""#)
+        );
+    }
+
+    #[test]
+    fn malformed_array_is_an_error() {
+        assert!(parse("[{\"url\": \"https://example.com/a\"}").is_err());
+    }
+}
@@ -1,31 +1,68 @@
 mod args;
+mod autopilot;
+mod bandwidth;
+mod checksum;
+mod checksum_manifest;
 mod client;
+mod conflict;
 mod dlm_error;
+mod domain_limiter;
+mod download_entry;
 mod downloader;
 mod file_link;
 mod headers;
+mod html_links;
+mod json_input;
+mod metalink;
+mod part_lock;
 mod progress_bar_manager;
 mod retry;
+mod schedule;
+mod segment;
+mod sidecar;
+mod template;
+mod url_pattern;
 mod user_agents;
 mod utils;
 
 use crate::DlmError::EmptyInputFile;
 use crate::args::{Arguments, Input, get_args};
+use crate::autopilot::Autopilot;
+use crate::checksum_manifest::ChecksumManifest;
 use crate::client::ClientConfig;
 use crate::dlm_error::DlmError;
-use crate::downloader::DownloadContext;
+use crate::domain_limiter::DomainLimiter;
+use crate::download_entry::{DownloadEntry, EntryParser, is_empty_line, is_option_line};
+use crate::downloader::{DownloadConfig, DownloadContext};
 use crate::progress_bar_manager::ProgressBarManager;
-use crate::retry::{retry_handler, retry_strategy, with_retries};
-use futures_util::stream::StreamExt;
+use crate::retry::{is_retryable_error, retry_handler, retry_strategy, with_retries};
+use crate::schedule::interleave_by_host;
+use futures_util::stream::{self, StreamExt};
+use indicatif::ProgressBar;
 use std::pin::Pin;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
 use tokio::io::AsyncBufReadExt;
 use tokio::{fs as tfs, signal};
 use tokio_stream::Stream;
 use tokio_stream::wrappers::LinesStream;
 use tokio_util::sync::CancellationToken;
 
-// type alias for the URL stream
-type LineStream = Pin<Box<dyn Stream<Item = Result<String, std::io::Error>> + Send>>;
+// type alias for the stream of entries to download, or of messages for the
+// input lines that could not be turned into one
+type EntryStream = Pin<Box<dyn Stream<Item = Result<DownloadEntry, String>> + Send>>;
+
+/// How often a followed input file is checked for appended lines.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long stdin or a followed input file may stay silent before the entry
+/// read last is downloaded without waiting for the line after it.
+const IDLE_INPUT_FLUSH: Duration = Duration::from_millis(500);
+
+/// With a per-host limit, number of entries that may wait for their host
+/// per global download slot.
+const QUEUED_ENTRIES_PER_SLOT: usize = 16;
 
 #[tokio::main]
 async fn main() {
@@ -43,7 +80,23 @@ async fn main_result() -> Result<(), DlmError> {
     // CLI args
     let Arguments {
         input,
+        interleave_domains,
+        follow,
+        glob,
+        link_filter,
+        crawl_depth,
+        checksums,
+        keep_paths,
+        output_template,
+        on_conflict,
+        update,
+        remote_time,
         max_concurrent_downloads,
+        max_concurrent_downloads_per_domain,
+        autopilot,
+        segments,
+        max_bandwidth,
+        max_bandwidth_per_download,
         output_dir,
         user_agent,
         proxy,
@@ -59,11 +112,49 @@ async fn main_result() -> Result<(), DlmError> {
     let token = CancellationToken::new();
     let signal_task_handler = spawn_signal_handler(token.clone());
 
+    let client_config = ClientConfig {
+        user_agent: user_agent.as_ref(),
+        proxy: proxy.as_deref(),
+        connection_timeout_secs,
+        read_timeout_secs,
+        insecure,
+        basic_auth: basic_auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str())),
+        headers: &headers,
+    };
+
+    // Metalink documents, JSON input files and HTML pages are parsed up front
+    // to count their entries
+    let preloaded_entries = match &input {
+        Input::Metalink(path) => metalink::parse(&tfs::read_to_string(path).await?)?
+            .into_iter()
+            .map(Ok)
+            .collect(),
+        Input::Json(path) => json_input::parse(&tfs::read_to_string(path).await?)?,
+        Input::Html(source) => match crawl_depth {
+            Some(depth) => html_links::crawl(source, &client_config, &link_filter, depth).await?,
+            None => html_links::load(source, &client_config, &link_filter)
+                .await?
+                .into_iter()
+                .map(Ok)
+                .collect(),
+        },
+        Input::File(_) | Input::Stdin | Input::Url { .. } => Vec::new(),
+    };
+
+    // unknown for inputs read while downloading
     let nb_of_lines = match &input {
-        Input::File(input_file) => count_non_empty_lines(input_file).await?,
-        Input::Url(_) => 1,
+        Input::File(_) if follow => None,
+        Input::File(input_file) => Some(count_input_file_downloads(input_file, glob).await?),
+        Input::Stdin => None,
+        Input::Url { url, .. } => Some(count_downloads(url, glob)),
+        Input::Metalink(_) | Input::Json(_) | Input::Html(_) => Some(
+            preloaded_entries
+                .iter()
+                .map(|entry| entry.as_ref().map_or(1, |e| count_downloads(&e.url, glob)))
+                .sum(),
+        ),
     };
-    if nb_of_lines == 0 {
+    if nb_of_lines == Some(0) {
         return Err(EmptyInputFile);
     }
 
@@ -71,22 +162,75 @@ async fn main_result() -> Result<(), DlmError> {
     let pbm = ProgressBarManager::init(max_concurrent_downloads, nb_of_lines).await;
     let pbm = &pbm;
 
-    let stream = build_url_stream(input, pbm, max_concurrent_downloads, nb_of_lines).await?;
+    let stream = build_url_stream(
+        input,
+        preloaded_entries,
+        follow,
+        pbm,
+        max_concurrent_downloads,
+        nb_of_lines,
+    )
+    .await?;
+    let stream = if glob {
+        expand_url_patterns(stream)
+    } else {
+        stream
+    };
+    let stream = if interleave_domains {
+        interleave_entries(stream).await
+    } else {
+        stream
+    };
 
     let token = &token;
-    let client_config = ClientConfig {
-        user_agent: user_agent.as_ref(),
-        proxy: proxy.as_deref(),
-        connection_timeout_secs,
-        read_timeout_secs,
-        insecure,
-        basic_auth: basic_auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str())),
-        headers: &headers,
+    let checksum_manifest = match checksums {
+        Some(source) => {
+            let manifest = ChecksumManifest::load(&source, &client_config).await?;
+            pbm.log_above_progress_bars(&format!(
+                "Loaded {} checksums from {source}",
+                manifest.len()
+            ));
+            Some(manifest)
+        }
+        None => None,
     };
-    let ctx = DownloadContext::new(&client_config, output_dir.as_path(), token, pbm)?;
+    let download_config = DownloadConfig {
+        segments,
+        max_bandwidth,
+        max_bandwidth_per_download,
+        checksum_manifest,
+        update,
+        remote_time,
+        on_conflict,
+        keep_paths,
+        output_template,
+    };
+    let ctx = DownloadContext::new(
+        &client_config,
+        download_config,
+        output_dir.as_path(),
+        token,
+        pbm,
+    )?;
     let ctx = &ctx;
 
-    process_downloads(stream, ctx, token, pbm, retry, max_concurrent_downloads).await;
+    let autopilot = autopilot.then(|| Autopilot::new(max_concurrent_downloads));
+    if let Some(autopilot) = &autopilot {
+        pbm.log_above_progress_bars(&format!(
+            "Autopilot enabled, starting with {} concurrent downloads",
+            autopilot.target()
+        ));
+        pbm.resize_pool(u64::from(autopilot.target()));
+    }
+
+    let scheduling = Scheduling {
+        retry,
+        max_concurrent_downloads,
+        domain_limiter: max_concurrent_downloads_per_domain.map(DomainLimiter::new),
+        autopilot,
+    };
+
+    let checksum_failures = process_downloads(stream, ctx, token, pbm, &scheduling).await;
 
     // stop signal handling
     signal_task_handler.abort();
@@ -94,7 +238,13 @@ async fn main_result() -> Result<(), DlmError> {
         Err(DlmError::ProgramInterrupted)
     } else {
         pbm.finish_all().await?;
-        Ok(())
+        if checksum_failures.is_empty() {
+            Ok(())
+        } else {
+            Err(DlmError::ChecksumVerificationFailed {
+                links: checksum_failures,
+            })
+        }
     }
 }
 
@@ -116,69 +266,238 @@ fn spawn_signal_handler(token: CancellationToken) -> tokio::task::JoinHandle<()>
 
 async fn build_url_stream(
     input: Input,
+    preloaded_entries: Vec<Result<DownloadEntry, String>>,
+    follow: bool,
     pbm: &ProgressBarManager,
     max_concurrent_downloads: u32,
-    nb_of_lines: u64,
-) -> Result<LineStream, DlmError> {
-    match input {
+    nb_of_lines: Option<u64>,
+) -> Result<EntryStream, DlmError> {
+    let entries = match input {
         Input::File(input_file) => {
             pbm.log_above_progress_bars(&format!(
                 "Starting dlm with at most {max_concurrent_downloads} concurrent downloads"
             ));
+            let file = tfs::File::open(&input_file).await?;
+            if follow {
+                pbm.log_above_progress_bars(&format!(
+                    "Following input file {input_file} for new URLs"
+                ));
+                warn_ignored_priorities(parse_lines(follow_lines(file), true), pbm)
+            } else {
+                let nb_of_lines = nb_of_lines.unwrap_or_default();
+                pbm.log_above_progress_bars(&format!(
+                    "Found {nb_of_lines} URLs in input file {input_file}"
+                ));
+                let file_reader = tokio::io::BufReader::new(file);
+                let lines = LinesStream::new(file_reader.lines());
+                warn_ignored_priorities(parse_lines(lines, false), pbm)
+            }
+        }
+        Input::Stdin => {
             pbm.log_above_progress_bars(&format!(
-                "Found {nb_of_lines} URLs in input file {input_file}"
+                "Starting dlm with at most {max_concurrent_downloads} concurrent downloads"
             ));
-            let file = tfs::File::open(input_file).await?;
-            let file_reader = tokio::io::BufReader::new(file);
-            Ok(Box::pin(LinesStream::new(file_reader.lines())))
+            pbm.log_above_progress_bars("Reading URLs from stdin");
+            let stdin_reader = tokio::io::BufReader::new(tokio::io::stdin());
+            let lines = LinesStream::new(stdin_reader.lines());
+            warn_ignored_priorities(parse_lines(lines, true), pbm)
         }
-        Input::Url(url) => {
+        Input::Url { url, output } => {
             pbm.log_above_progress_bars(&format!("Downloading single URL: {url}"));
-            Ok(Box::pin(tokio_stream::once(Ok(url))))
+            let mut entry = DownloadEntry::new(&url);
+            entry.filename = output;
+            Box::pin(tokio_stream::once(Ok(entry)))
         }
-    }
+        Input::Metalink(path) => {
+            pbm.log_above_progress_bars(&format!(
+                "Starting dlm with at most {max_concurrent_downloads} concurrent downloads"
+            ));
+            let nb_of_files = nb_of_lines.unwrap_or_default();
+            pbm.log_above_progress_bars(&format!("Found {nb_of_files} files in Metalink {path}"));
+            Box::pin(tokio_stream::iter(preloaded_entries))
+        }
+        Input::Json(path) => {
+            pbm.log_above_progress_bars(&format!(
+                "Starting dlm with at most {max_concurrent_downloads} concurrent downloads"
+            ));
+            let nb_of_entries = nb_of_lines.unwrap_or_default();
+            pbm.log_above_progress_bars(&format!(
+                "Found {nb_of_entries} entries in JSON input file {path}"
+            ));
+            Box::pin(tokio_stream::iter(preloaded_entries))
+        }
+        Input::Html(source) => {
+            pbm.log_above_progress_bars(&format!(
+                "Starting dlm with at most {max_concurrent_downloads} concurrent downloads"
+            ));
+            let nb_of_links = nb_of_lines.unwrap_or_default();
+            pbm.log_above_progress_bars(&format!("Found {nb_of_links} links in {source}"));
+            Box::pin(tokio_stream::iter(preloaded_entries))
+        }
+    };
+    Ok(entries)
+}
+
+/// Replace each entry by one entry per URL of its URL pattern. The URLs of a
+/// pattern are numbered on from its index, the later entries shifted after
+/// them so that every download keeps its own index.
+fn expand_url_patterns(entries: EntryStream) -> EntryStream {
+    let mut shift = 0_usize;
+    Box::pin(entries.flat_map(move |entry| -> EntryStream {
+        let entry = match entry {
+            Ok(entry) => DownloadEntry {
+                index: entry.index.saturating_add(shift),
+                ..entry
+            },
+            Err(message) => return Box::pin(tokio_stream::once(Err(message))),
+        };
+        let extra_urls = url_pattern::count_urls(&entry.url) - 1;
+        shift = shift.saturating_add(usize::try_from(extra_urls).unwrap_or(usize::MAX));
+        let url = entry.url.clone();
+        match url_pattern::expand_entry(entry) {
+            Ok(expanded) => Box::pin(tokio_stream::iter(expanded.map(Ok))),
+            Err(e) => Box::pin(tokio_stream::once(Err(format!("Error for {url}: {e}")))),
+        }
+    }))
+}
+
+/// Reorder the entries round-robin by host, loading the whole input.
+async fn interleave_entries(entries: EntryStream) -> EntryStream {
+    let entries: Vec<_> = entries.collect().await;
+    let entries = interleave_by_host(entries, |entry| {
+        entry.as_ref().map_or("", |entry| entry.url.as_str())
+    });
+    Box::pin(tokio_stream::iter(entries))
+}
+
+/// Lines of `file`, waiting for more to be appended at its end. A line is
+/// only yielded once its newline was written.
+fn follow_lines(
+    file: tfs::File,
+) -> impl Stream<Item = Result<String, std::io::Error>> + Send + 'static {
+    let state = (tokio::io::BufReader::new(file), String::new());
+    stream::unfold(state, |(mut reader, mut line)| async move {
+        loop {
+            match reader.read_line(&mut line).await {
+                Err(e) => return Some((Err(e), (reader, line))),
+                Ok(_) if line.ends_with('\n') => {
+                    let complete = std::mem::take(&mut line);
+                    let complete = complete.trim_end_matches(['\n', '\r']).to_string();
+                    return Some((Ok(complete), (reader, line)));
+                }
+                // end of the file for now, the partial line is kept
+                Ok(_) => tokio::time::sleep(FOLLOW_POLL_INTERVAL).await,
+            }
+        }
+    })
+}
+
+/// Turn the lines of an input file into entries, skipping comments and
+/// blank lines. An entry is only yielded once the next URL line shows all
+/// its option lines were read, or with `flush_when_idle` once no line came
+/// for `IDLE_INPUT_FLUSH`, so a slow producer does not hold back its last URL.
+fn parse_lines(
+    lines: impl Stream<Item = Result<String, std::io::Error>> + Send + 'static,
+    flush_when_idle: bool,
+) -> EntryStream {
+    let state = Some((
+        Box::pin(lines.zip(stream::iter(1..))),
+        EntryParser::default(),
+    ));
+    Box::pin(stream::unfold(state, move |state| async move {
+        let (mut lines, mut parser) = state?;
+        loop {
+            let next = if flush_when_idle {
+                match tokio::time::timeout(IDLE_INPUT_FLUSH, lines.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        if let Some(entry) = parser.flush() {
+                            return Some((entry, Some((lines, parser))));
+                        }
+                        continue;
+                    }
+                }
+            } else {
+                lines.next().await
+            };
+            match next {
+                Some((Err(e), _)) => {
+                    let message = format!("Error with links iterator {e}");
+                    return Some((Err(message), Some((lines, parser))));
+                }
+                Some((Ok(line), _)) if is_empty_line(&line) => {}
+                Some((Ok(line), line_number)) => {
+                    if let Some(entry) = parser.push_line(line_number, &line) {
+                        return Some((entry, Some((lines, parser))));
+                    }
+                }
+                None => return parser.finish().map(|entry| (entry, None)),
+            }
+        }
+    }))
+}
+
+/// Warn once when JSON entries read line by line set a priority: they are
+/// downloaded in the order they come.
+fn warn_ignored_priorities(entries: EntryStream, pbm: &ProgressBarManager) -> EntryStream {
+    let log = pbm.logger();
+    let mut warned = false;
+    Box::pin(entries.inspect(move |entry| {
+        if !warned && let Ok(DownloadEntry { priority: Some(_), .. }) = entry {
+            warned = true;
+            log("Ignoring the priority of JSON entries read line by line, they are downloaded in the order they come");
+        }
+    }))
+}
+
+/// How downloads are scheduled and retried.
+struct Scheduling {
+    retry: u32,
+    max_concurrent_downloads: u32,
+    domain_limiter: Option<DomainLimiter>,
+    autopilot: Option<Autopilot>,
 }
 
 async fn process_downloads(
-    stream: LineStream,
+    stream: EntryStream,
     ctx: &DownloadContext<'_>,
     token: &CancellationToken,
     pbm: &ProgressBarManager,
-    retry: u32,
-    max_concurrent_downloads: u32,
-) {
+    scheduling: &Scheduling,
+) -> Vec<String> {
+    // links whose download did not match its expected checksum
+    let checksum_failures = Mutex::new(Vec::new());
+    let checksum_failures_ref = &checksum_failures;
+    let domain_limiter = scheduling.domain_limiter.as_ref();
+    // With a per-domain limit links are read ahead of the free slots: the
+    // ones queued behind a busy host must not hold the slots of other hosts.
+    // The global limit is then enforced by the progress bar pool, and the
+    // read-ahead is bounded so a huge input is not held in memory at once.
+    let max_concurrent_downloads = scheduling.max_concurrent_downloads as usize;
+    let concurrency = match domain_limiter {
+        Some(_) => max_concurrent_downloads * QUEUED_ENTRIES_PER_SLOT,
+        None => max_concurrent_downloads,
+    };
     stream
+        .inspect(|_| pbm.extend_open_ended_length())
         .take_until(token.cancelled())
-        .for_each_concurrent(max_concurrent_downloads as usize, |link_res| async move {
+        .for_each_concurrent(Some(concurrency), |entry_res| async move {
             if token.is_cancelled() {
                 return;
             }
-            let message = match link_res {
-                Err(e) => Some(format!("Error with links iterator {e}")),
-                Ok(link) => {
-                    if is_empty_line(&link) {
-                        None
-                    } else {
-                        // claim a progress bar for the upcoming download
-                        let dl_pb = pbm.claim_progress_bar().await;
-
-                        // polite fixed-then-exponential retries for network errors
-                        let processed = with_retries(
-                            retry_strategy(retry),
-                            || ctx.download_link(&link, &dl_pb),
-                            |e: &DlmError| retry_handler(e, pbm, &link),
-                        )
-                        .await;
-
-                        // reset & release progress bar
-                        pbm.release_progress_bar(dl_pb).await;
-
-                        match processed {
-                            Ok(info) => Some(info),
-                            Err(DlmError::ProgramInterrupted) => None,
-                            Err(e) => Some(format!("Error for {link}: {e}")),
-                        }
+            let message = match entry_res {
+                Err(message) => Some(message),
+                Ok(entry) => {
+                    // wait for the host to have a free slot before claiming
+                    // one of the global ones
+                    let _domain_permit = match domain_limiter {
+                        Some(limiter) => limiter.acquire(&entry.url).await,
+                        None => None,
+                    };
+                    if token.is_cancelled() {
+                        return;
                     }
+                    download_entry(ctx, pbm, scheduling, &entry, checksum_failures_ref).await
                 }
             };
             if let Some(message) = message {
@@ -187,21 +506,131 @@ async fn process_downloads(
             }
         })
         .await;
+    checksum_failures
+        .into_inner()
+        .expect("checksum failures lock poisoned")
 }
 
-fn is_empty_line(line: &str) -> bool {
-    let line = line.trim();
-    line.is_empty() || line.starts_with('#')
+/// Download a single entry with a claimed progress bar and retries. Returns
+/// the message to log, `None` when interrupted.
+async fn download_entry(
+    ctx: &DownloadContext<'_>,
+    pbm: &ProgressBarManager,
+    scheduling: &Scheduling,
+    entry: &DownloadEntry,
+    checksum_failures: &Mutex<Vec<String>>,
+) -> Option<String> {
+    let autopilot = scheduling.autopilot.as_ref();
+    let link = &entry.url;
+
+    // claim a progress bar for the upcoming download
+    let dl_pb = pbm.claim_progress_bar().await;
+    let started = Instant::now();
+    // partial data found on disk by the last attempt, not transferred now
+    let resumed_bytes = AtomicU64::new(0);
+
+    // polite fixed-then-exponential retries for network errors, each retry
+    // going through the mirrors again
+    let processed = with_retries(
+        retry_strategy(scheduling.retry),
+        || download_from_mirrors(ctx, pbm, entry, &dl_pb, &resumed_bytes),
+        |e: &DlmError| {
+            let should_retry = retry_handler(e, pbm, link);
+            if should_retry && let Some(autopilot) = autopilot {
+                apply_autopilot_target(pbm, autopilot.on_retryable_error());
+            }
+            should_retry
+        },
+    )
+    .await;
+
+    if processed.is_ok()
+        && let Some(autopilot) = autopilot
+    {
+        let target = autopilot.on_success(
+            dl_pb.position(),
+            resumed_bytes.load(AtomicOrdering::Relaxed),
+            started.elapsed(),
+        );
+        apply_autopilot_target(pbm, target);
+    }
+
+    // reset & release progress bar
+    pbm.release_progress_bar(dl_pb).await;
+
+    match processed {
+        Ok(info) => Some(info),
+        Err(DlmError::ProgramInterrupted) => None,
+        Err(e @ DlmError::ChecksumMismatch { .. }) => {
+            checksum_failures
+                .lock()
+                .expect("checksum failures lock poisoned")
+                .push(link.clone());
+            Some(format!("Error for {link}: {e}"))
+        }
+        Err(e) => Some(format!("Error for {link}: {e}")),
+    }
+}
+
+/// Download `entry` from its URL, moving on to the next mirror after each
+/// retryable error, or when a mirror does not serve the expected size.
+/// Returns the error of the last URL tried.
+async fn download_from_mirrors(
+    ctx: &DownloadContext<'_>,
+    pbm: &ProgressBarManager,
+    entry: &DownloadEntry,
+    dl_pb: &ProgressBar,
+    resumed_bytes: &AtomicU64,
+) -> Result<String, DlmError> {
+    let mut urls = entry.urls().peekable();
+    loop {
+        let url = urls.next().expect("an entry has at least one URL");
+        match ctx.download_link(entry, url, dl_pb, resumed_bytes).await {
+            Err(e)
+                if (is_retryable_error(&e) || matches!(e, DlmError::SizeMismatch { .. }))
+                    && urls.peek().is_some() =>
+            {
+                pbm.log_above_progress_bars(&format!(
+                    "Error for {url}: {e}, trying the next mirror"
+                ));
+            }
+            processed => return processed,
+        }
+    }
+}
+
+/// Resize the progress bar pool, which caps the number of concurrent
+/// downloads, to the new autopilot target if there is one.
+fn apply_autopilot_target(pbm: &ProgressBarManager, target: Option<u32>) {
+    if let Some(target) = target {
+        pbm.log_above_progress_bars(&format!(
+            "Autopilot adjusting to {target} concurrent downloads"
+        ));
+        pbm.resize_pool(u64::from(target));
+    }
+}
+
+/// Number of downloads `url` stands for, more than one for a URL pattern
+/// with `glob`.
+fn count_downloads(url: &str, glob: bool) -> u64 {
+    if glob {
+        url_pattern::count_urls(url)
+    } else {
+        1
+    }
 }
 
-async fn count_non_empty_lines(input_file: &str) -> Result<u64, DlmError> {
+/// Number of downloads of `input_file`, a URL pattern counting as many as it
+/// expands to, as in `expand_url_patterns`.
+async fn count_input_file_downloads(input_file: &str, glob: bool) -> Result<u64, DlmError> {
     let file = tfs::File::open(input_file).await?;
     let reader = tokio::io::BufReader::new(file);
     let mut lines = reader.lines();
     let mut count = 0;
     while let Some(line) = lines.next_line().await? {
-        if !is_empty_line(&line) {
-            count += 1;
+        if !is_empty_line(&line) && !is_option_line(&line) {
+            count += DownloadEntry::parse_line(&line)
+                .map_or(1, |entry| count_downloads(&entry.url, glob));
         }
     }
     Ok(count)
@@ -0,0 +1,183 @@
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::dlm_error::DlmError;
+use crate::download_entry::DownloadEntry;
+use roxmltree::Node;
+
+/// Namespace of Metalink 4 documents (RFC 5854).
+const METALINK_NS: &str = "urn:ietf:params:xml:ns:metalink";
+
+/// Lowest priority a `<url>` can have, used when the attribute is missing.
+const LOWEST_PRIORITY: u32 = 999_999;
+
+/// Parse a Metalink 4 document (`.meta4`) into one entry per `<file>`.
+///
+/// The URLs of a file are ordered by priority, the first one being the
+/// entry's URL and the others its mirrors. The strongest supported `<hash>`
+/// becomes the entry's checksum; the others are ignored.
+pub fn parse(content: &str) -> Result<Vec<DownloadEntry>, DlmError> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|e| DlmError::other(format!("invalid Metalink document - {e}")))?;
+    let root = document.root_element();
+    if !root.has_tag_name((METALINK_NS, "metalink")) {
+        return Err(DlmError::other(
+            "invalid Metalink document - expected a Metalink 4 <metalink> root".to_string(),
+        ));
+    }
+    children(root, "file")
+        .zip(1..)
+        .map(|(file, index)| {
+            let mut entry = parse_file(file)?;
+            entry.index = index;
+            Ok(entry)
+        })
+        .collect()
+}
+
+fn parse_file(file: Node<'_, '_>) -> Result<DownloadEntry, DlmError> {
+    let name = file.attribute("name").ok_or_else(|| {
+        DlmError::other("invalid Metalink document - <file> without a name".to_string())
+    })?;
+
+    let mut urls: Vec<(u32, &str)> = children(file, "url")
+        .filter_map(|url| {
+            let priority = url
+                .attribute("priority")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(LOWEST_PRIORITY);
+            Some((priority, url.text()?.trim()))
+        })
+        .filter(|(_, url)| !url.is_empty())
+        .collect();
+    // stable: URLs sharing a priority keep the document order
+    urls.sort_by_key(|(priority, _)| *priority);
+    let mut urls = urls.into_iter().map(|(_, url)| url.to_string());
+    let url = urls.next().ok_or_else(|| {
+        DlmError::other(format!("invalid Metalink document - no <url> for '{name}'"))
+    })?;
+
+    let size = match children(file, "size").next().and_then(|s| s.text()) {
+        Some(size) => Some(size.trim().parse().map_err(|_| {
+            DlmError::other(format!("invalid Metalink document - bad size for '{name}'"))
+        })?),
+        None => None,
+    };
+
+    let checksum = children(file, "hash")
+        .filter_map(|hash| {
+            let algorithm = HashAlgorithm::from_name(hash.attribute("type")?)?;
+            Some((algorithm, hash.text()?))
+        })
+        .max_by_key(|(algorithm, _)| *algorithm)
+        .map(|(algorithm, hex)| Checksum::new(algorithm, hex))
+        .transpose()?;
+
+    let mut entry = DownloadEntry::new(&url);
+    entry.mirrors = urls.collect();
+    entry.filename = Some(name.to_string());
+    entry.size = size;
+    entry.checksum = checksum;
+    Ok(entry)
+}
+
+/// Metalink elements named `name` directly under `node`.
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.has_tag_name((METALINK_NS, name)))
+}
+
+#[cfg(test)]
+mod metalink_tests {
+    use super::*;
+
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn document(files: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <published>2024-01-01T00:00:00Z</published>
+  {files}
+</metalink>"#
+        )
+    }
+
+    #[test]
+    fn file_with_mirrors_size_and_hash() {
+        let content = document(&format!(
+            r#"<file name="example.iso">
+    <size>14471447</size>
+    <hash type="sha-256">{SHA256}</hash>
+    <url location="de" priority="2">https://de.example.com/example.iso</url>
+    <url location="us" priority="1">https://us.example.com/example.iso</url>
+    <url>https://fallback.example.com/example.iso</url>
+  </file>"#
+        ));
+        let entries = parse(&content).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.url, "https://us.example.com/example.iso");
+        assert_eq!(
+            entry.mirrors,
+            vec![
+                "https://de.example.com/example.iso",
+                "https://fallback.example.com/example.iso"
+            ]
+        );
+        assert_eq!(entry.filename.as_deref(), Some("example.iso"));
+        assert_eq!(entry.size, Some(14_471_447));
+        assert_eq!(entry.checksum.as_ref().unwrap().expected, SHA256);
+    }
+
+    #[test]
+    fn strongest_supported_hash_wins() {
+        let sha512 = "a".repeat(128);
+        let content = document(&format!(
+            r#"<file name="a.bin">
+    <hash type="md5">900150983cd24fb0d6963f7d28e17f72</hash>
+    <hash type="sha-512">{sha512}</hash>
+    <hash type="sha-256">{SHA256}</hash>
+    <url>https://example.com/a.bin</url>
+  </file>"#
+        ));
+        let checksum = parse(&content).unwrap()[0].checksum.clone().unwrap();
+        assert_eq!(checksum.algorithm, HashAlgorithm::Sha512);
+        assert_eq!(checksum.expected, sha512);
+    }
+
+    #[test]
+    fn several_files_without_optional_elements() {
+        let content = document(
+            r#"<file name="a.bin"><url>https://example.com/a.bin</url></file>
+  <file name="b.bin"><url>https://example.com/b.bin</url></file>"#,
+        );
+        let entries = parse(&content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].url, "https://example.com/b.bin");
+        assert_eq!(entries[1].size, None);
+        assert_eq!(entries[1].checksum, None);
+        assert!(entries[1].mirrors.is_empty());
+    }
+
+    #[test]
+    fn file_without_url_is_an_error() {
+        let content = document(r#"<file name="a.bin"><size>3</size></file>"#);
+        assert!(parse(&content).is_err());
+    }
+
+    #[test]
+    fn invalid_size_is_an_error() {
+        let content =
+            document(r#"<file name="a.bin"><size>big</size><url>https://e.com/a</url></file>"#);
+        assert!(parse(&content).is_err());
+    }
+
+    #[test]
+    fn other_documents_are_rejected() {
+        assert!(parse("not xml").is_err());
+        // Metalink 3 uses another namespace and layout
+        assert!(parse(r#"<metalink xmlns="http://www.metalinker.org/"/>"#).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::dlm_error::DlmError;
+use std::fs::{File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Advisory lock (`flock` on Linux) guarding a `.part` and its segments
+/// against other dlm processes sharing the output directory. It is held on
+/// `file.bin.part.lock`, which names the holding process.
+pub struct PartLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl PartLock {
+    /// On-disk location of the lock of the `.part` file `tmp_name`.
+    pub fn path(tmp_name: &Path) -> PathBuf {
+        let mut name = tmp_name.as_os_str().to_owned();
+        name.push(".lock");
+        PathBuf::from(name)
+    }
+
+    /// Take the lock of `tmp_name` without waiting. Fails with `PartLocked`
+    /// if another process holds it.
+    pub async fn acquire(tmp_name: &Path) -> Result<Self, DlmError> {
+        let tmp_name = tmp_name.to_path_buf();
+        tokio::task::spawn_blocking(move || Self::acquire_blocking(&tmp_name)).await?
+    }
+
+    fn acquire_blocking(tmp_name: &Path) -> Result<Self, DlmError> {
+        let path = Self::path(tmp_name);
+        loop {
+            let mut file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    let pid = std::fs::read_to_string(&path).unwrap_or_default();
+                    let pid = pid.trim();
+                    return Err(DlmError::PartLocked {
+                        path: tmp_name.display().to_string(),
+                        pid: if pid.is_empty() { "unknown" } else { pid }.to_string(),
+                    });
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+            // the previous holder may have removed the file between its
+            // opening and its locking here: another process can then lock
+            // the one now at `path`, so the lock is taken again on it
+            if !is_file_at(&file, &path)? {
+                continue;
+            }
+            file.set_len(0)?;
+            write!(file, "{}", std::process::id())?;
+            return Ok(Self { file, path });
+        }
+    }
+}
+
+/// Whether the open `file` is still the one found at `path`.
+#[cfg(unix)]
+fn is_file_at(file: &File, path: &Path) -> Result<bool, DlmError> {
+    use std::io::ErrorKind;
+    use std::os::unix::fs::MetadataExt;
+    let current = match std::fs::metadata(path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let opened = file.metadata()?;
+    Ok(opened.dev() == current.dev() && opened.ino() == current.ino())
+}
+
+/// Whether the open `file` is still the one found at `path`, always the
+/// case where lock files are never removed.
+#[cfg(not(unix))]
+fn is_file_at(_file: &File, _path: &Path) -> Result<bool, DlmError> {
+    Ok(true)
+}
+
+impl Drop for PartLock {
+    fn drop(&mut self) {
+        // removed while still locked: a process that opened it before sees
+        // it is gone once it gets the lock, and starts over on a new file.
+        // Without a way to tell files apart, it is left in place instead.
+        // Best effort, the lock itself goes with `file`
+        if cfg!(unix) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod part_lock_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    #[test]
+    fn path_appends_suffix() {
+        let path = PartLock::path(Path::new("/tmp/file.bin.part"));
+        assert_eq!(path, PathBuf::from("/tmp/file.bin.part.lock"));
+    }
+
+    #[tokio::test]
+    async fn second_acquire_names_the_holder() {
+        let dir = tempdir().unwrap();
+        let part = dir.path().join("file.bin.part");
+        let lock = PartLock::acquire(&part).await.unwrap();
+
+        match PartLock::acquire(&part).await {
+            Err(DlmError::PartLocked { pid, .. }) => {
+                assert_eq!(pid, std::process::id().to_string());
+            }
+            other => panic!("expected PartLocked, got {:?}", other.map(|_| ())),
+        }
+
+        drop(lock);
+        assert_eq!(PartLock::path(&part).exists(), !cfg!(unix));
+        PartLock::acquire(&part).await.unwrap();
+    }
+
+    #[test]
+    fn contended_lock_has_a_single_holder() {
+        let dir = tempdir().unwrap();
+        let part = dir.path().join("file.bin.part");
+        let holders = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..500 {
+                        if let Ok(lock) = PartLock::acquire_blocking(&part) {
+                            assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                            std::thread::yield_now();
+                            holders.fetch_sub(1, Ordering::SeqCst);
+                            drop(lock);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
@@ -6,18 +6,30 @@ use indicatif::{
 };
 use jiff::Zoned;
 use std::cmp::{Ordering, min};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 
 const PENDING: &str = "pending";
 
 pub struct ProgressBarManager {
+    mp: MultiProgress,
     main_pb: ProgressBar,
+    /// The number of entries is unknown up front, the main progress bar
+    /// grows as they are read.
+    open_ended: bool,
     file_pb_count: u64,
+    /// Number of file progress bars that may be claimed at once, at most
+    /// `file_pb_count`. The bars above it are parked out of the pool.
+    pool_size: AtomicU64,
+    // `std::sync::Mutex` is fine here — the lock is never held across an `.await`
+    parked: Mutex<Vec<ProgressBar>>,
     tx: Sender<ProgressBar>,
     rx: Receiver<ProgressBar>,
 }
 
 impl ProgressBarManager {
-    pub async fn init(max_concurrent_downloads: u32, main_pb_len: u64) -> Self {
+    /// `main_pb_len` is `None` when the entries are read while downloading.
+    pub async fn init(max_concurrent_downloads: u32, main_pb_len: Option<u64>) -> Self {
         let mp = MultiProgress::new();
         // Refresh terminal 5 times per seconds
         let draw_target = ProgressDrawTarget::stdout_with_hz(5);
@@ -29,10 +41,13 @@ impl ProgressBarManager {
             .expect("templating should not fail");
         let main_pb = mp.add(ProgressBar::new(0));
         main_pb.set_style(main_style);
-        main_pb.set_length(main_pb_len);
+        main_pb.set_length(main_pb_len.unwrap_or(0));
 
         // `file_pb_count` progress bars are shared between the threads at anytime
-        let file_pb_count = min(u64::from(max_concurrent_downloads), main_pb_len);
+        let file_pb_count = match main_pb_len {
+            Some(len) => min(u64::from(max_concurrent_downloads), len),
+            None => u64::from(max_concurrent_downloads),
+        };
 
         let (tx, rx): (Sender<ProgressBar>, Receiver<ProgressBar>) =
             async_channel::bounded(file_pb_count as usize);
@@ -71,15 +86,23 @@ impl ProgressBarManager {
         }
 
         Self {
+            mp,
             main_pb,
+            open_ended: main_pb_len.is_none(),
             file_pb_count,
+            pool_size: AtomicU64::new(file_pb_count),
+            parked: Mutex::new(Vec::new()),
             tx,
             rx,
         }
     }
 
     pub async fn finish_all(&self) -> Result<(), DlmError> {
-        for _ in 0..self.file_pb_count {
+        let parked: Vec<ProgressBar> = self.lock_parked().drain(..).collect();
+        for pb in &parked {
+            pb.finish_and_clear();
+        }
+        for _ in 0..self.file_pb_count - parked.len() as u64 {
             let pb = self.rx.recv().await?;
             pb.finish_and_clear();
         }
@@ -91,6 +114,13 @@ impl ProgressBarManager {
         self.main_pb.inc(1);
     }
 
+    /// Count an entry read from an input of unknown length.
+    pub fn extend_open_ended_length(&self) {
+        if self.open_ended {
+            self.main_pb.inc_length(1);
+        }
+    }
+
     const PROGRESS_BAR_MSG_WIDTH: usize = 35;
 
     pub fn message_progress_bar(s: &str) -> String {
@@ -108,6 +138,12 @@ impl ProgressBarManager {
         Self::log_above_progress_bar(&self.main_pb, msg);
     }
 
+    /// Log above the progress bars from a stream that cannot borrow the manager.
+    pub fn logger(&self) -> impl Fn(&str) + Send + 'static {
+        let main_pb = self.main_pb.clone();
+        move |msg| Self::log_above_progress_bar(&main_pb, msg)
+    }
+
     fn log_above_progress_bar(pb: &ProgressBar, msg: &str) {
         let now = Zoned::now().strftime("%Y-%m-%d %H:%M:%S");
         pb.println(format!("[{now}] {msg}"));
@@ -123,12 +159,58 @@ impl ProgressBarManager {
     pub async fn release_progress_bar(&self, pb: ProgressBar) {
         pb.reset();
         pb.set_message(Self::message_progress_bar(PENDING));
+        // the pool shrank while this bar was in use
+        if self.active_bar_count() > self.pool_size.load(AtomicOrdering::SeqCst) {
+            self.park(pb);
+            return;
+        }
         self.tx
             .send(pb)
             .await
             .expect("releasing progress bar should not fail");
     }
 
+    /// Change how many file progress bars can be claimed at once, which caps
+    /// the number of concurrent downloads. Idle bars are parked or brought
+    /// back right away; bars in use are parked when released.
+    pub fn resize_pool(&self, size: u64) {
+        let size = size.clamp(1, self.file_pb_count.max(1));
+        self.pool_size.store(size, AtomicOrdering::SeqCst);
+        while self.active_bar_count() > size {
+            match self.rx.try_recv() {
+                Ok(pb) => self.park(pb),
+                // the remaining excess bars are in use
+                Err(_) => break,
+            }
+        }
+        while self.active_bar_count() < size {
+            let Some(pb) = self.lock_parked().pop() else {
+                break;
+            };
+            let pb = self.mp.add(pb);
+            // the channel is sized to hold every bar, so it is never full
+            self.tx
+                .try_send(pb)
+                .expect("releasing progress bar should not fail");
+        }
+    }
+
+    /// Bars in the pool or claimed, i.e. not parked.
+    fn active_bar_count(&self) -> u64 {
+        self.file_pb_count - self.lock_parked().len() as u64
+    }
+
+    fn park(&self, pb: ProgressBar) {
+        self.mp.remove(&pb);
+        self.lock_parked().push(pb);
+    }
+
+    fn lock_parked(&self) -> std::sync::MutexGuard<'_, Vec<ProgressBar>> {
+        self.parked
+            .lock()
+            .expect("parked progress bars lock should not be poisoned")
+    }
+
     /// Test-only manager that draws nowhere, so unit tests can exercise code
     /// paths that log above the progress bars without touching the terminal.
     #[cfg(test)]
@@ -137,8 +219,12 @@ impl ProgressBarManager {
         let main_pb = mp.add(ProgressBar::hidden());
         let (tx, rx) = async_channel::bounded(1);
         Self {
+            mp,
             main_pb,
+            open_ended: false,
             file_pb_count: 0,
+            pool_size: AtomicU64::new(0),
+            parked: Mutex::new(Vec::new()),
             tx,
             rx,
         }
@@ -161,8 +247,12 @@ mod recycling_tests {
             tx.send(pb).await.unwrap();
         }
         ProgressBarManager {
+            mp,
             main_pb,
+            open_ended: false,
             file_pb_count: count as u64,
+            pool_size: AtomicU64::new(count as u64),
+            parked: Mutex::new(Vec::new()),
             tx,
             rx,
         }
@@ -218,4 +308,59 @@ mod recycling_tests {
             mgr.release_progress_bar(pb_check).await;
         }
     }
+
+    /// Shrinking the pool parks idle bars at once, so fewer downloads can
+    /// claim one; growing it hands them back.
+    #[tokio::test]
+    async fn resize_pool_parks_and_restores_idle_bars() {
+        let mgr = manager_with_bars(3).await;
+
+        mgr.resize_pool(1);
+        let pb = mgr.claim_progress_bar().await;
+        assert!(
+            mgr.rx.try_recv().is_err(),
+            "only one bar should be claimable"
+        );
+
+        mgr.resize_pool(3);
+        assert_eq!(mgr.rx.len(), 2);
+        mgr.release_progress_bar(pb).await;
+        assert_eq!(mgr.rx.len(), 3);
+    }
+
+    /// A bar in use when the pool shrinks is parked on release instead of
+    /// going back to the pool.
+    #[tokio::test]
+    async fn shrinking_pool_parks_bars_on_release() {
+        let mgr = manager_with_bars(2).await;
+        let first = mgr.claim_progress_bar().await;
+        let second = mgr.claim_progress_bar().await;
+
+        mgr.resize_pool(1);
+        mgr.release_progress_bar(first).await;
+        assert!(mgr.rx.is_empty(), "released bar should be parked");
+        mgr.release_progress_bar(second).await;
+        assert_eq!(mgr.rx.len(), 1);
+
+        // parked bars are still accounted for when finishing
+        mgr.finish_all().await.unwrap();
+    }
+
+    /// Only an open-ended main progress bar grows with the entries read.
+    #[tokio::test]
+    async fn open_ended_length_grows_with_entries() {
+        let mgr = manager_with_bars(1).await;
+        mgr.main_pb.set_length(0);
+        mgr.extend_open_ended_length();
+        assert_eq!(mgr.main_pb.length(), Some(0));
+
+        let mgr = ProgressBarManager {
+            open_ended: true,
+            ..manager_with_bars(1).await
+        };
+        mgr.main_pb.set_length(0);
+        mgr.extend_open_ended_length();
+        mgr.extend_open_ended_length();
+        assert_eq!(mgr.main_pb.length(), Some(2));
+    }
 }
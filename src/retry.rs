@@ -102,7 +102,7 @@ pub fn retry_handler(e: &DlmError, pbm: &ProgressBarManager, link: &str) -> bool
     should_retry
 }
 
-const fn is_retryable_error(e: &DlmError) -> bool {
+pub const fn is_retryable_error(e: &DlmError) -> bool {
     matches!(
         e,
         DlmError::ConnectError
@@ -0,0 +1,77 @@
+use crate::file_link::FileLink;
+
+/// Reorder `links` round-robin by the host of the URL given by `url`: the
+/// first link of every host, then the second of every host, and so on. Hosts
+/// take turns in the order they first appear, and links of a same host keep
+/// their relative order. Links without a parseable host are grouped together.
+pub fn interleave_by_host<T>(links: Vec<T>, url: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut groups: Vec<(String, Vec<T>)> = Vec::new();
+    for link in links {
+        let host = FileLink::new(url(&link))
+            .map(|fl| fl.host)
+            .unwrap_or_default();
+        match groups.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, group)) => group.push(link),
+            None => groups.push((host, vec![link])),
+        }
+    }
+
+    let total = groups.iter().map(|(_, group)| group.len()).sum();
+    let mut interleaved = Vec::with_capacity(total);
+    let mut iters: Vec<_> = groups
+        .into_iter()
+        .map(|(_, group)| group.into_iter())
+        .collect();
+    while interleaved.len() < total {
+        interleaved.extend(iters.iter_mut().filter_map(Iterator::next));
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod schedule_tests {
+    use super::*;
+
+    fn links(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn round_robin_across_hosts() {
+        let input = links(&[
+            "https://a.com/1.bin",
+            "https://a.com/2.bin",
+            "https://a.com/3.bin",
+            "https://b.com/1.bin",
+            "https://c.com/1.bin",
+            "https://b.com/2.bin",
+        ]);
+        let expected = links(&[
+            "https://a.com/1.bin",
+            "https://b.com/1.bin",
+            "https://c.com/1.bin",
+            "https://a.com/2.bin",
+            "https://b.com/2.bin",
+            "https://a.com/3.bin",
+        ]);
+        assert_eq!(interleave_by_host(input, String::as_str), expected);
+    }
+
+    #[test]
+    fn single_host_keeps_order() {
+        let input = links(&["https://a.com/1.bin", "https://a.com/2.bin"]);
+        assert_eq!(interleave_by_host(input.clone(), String::as_str), input);
+    }
+
+    #[test]
+    fn unparseable_links_are_kept() {
+        let input = links(&["not-a-url", "https://a.com/1.bin", "also-not-a-url"]);
+        let expected = links(&["not-a-url", "https://a.com/1.bin", "also-not-a-url"]);
+        assert_eq!(interleave_by_host(input, String::as_str), expected);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(interleave_by_host(Vec::new(), String::as_str).is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::dlm_error::DlmError;
+use std::path::{Path, PathBuf};
+use tokio::fs as tfs;
+use tokio::io::AsyncWriteExt;
+
+/// Split `total` bytes into at most `count` contiguous inclusive ranges
+/// `(start, end)`. The remainder is spread over the first ranges so sizes
+/// differ by at most one byte. Never yields an empty range, so fewer than
+/// `count` ranges come back for tiny bodies.
+pub fn split_ranges(total: u64, count: u32) -> Vec<(u64, u64)> {
+    let count = u64::from(count).min(total);
+    if count == 0 {
+        return Vec::new();
+    }
+    let base = total / count;
+    let remainder = total % count;
+    let mut ranges = Vec::with_capacity(count as usize);
+    let mut start = 0;
+    for i in 0..count {
+        let len = base + u64::from(i < remainder);
+        ranges.push((start, start + len - 1));
+        start += len;
+    }
+    ranges
+}
+
+/// On-disk location of segment `index` for the `.part` file `tmp_name`,
+/// e.g. `file.bin.part.0`. Each segment lives in its own file so an
+/// interrupted segmented download resumes every range from its own size.
+pub fn segment_path(tmp_name: &Path, index: usize) -> PathBuf {
+    let mut name = tmp_name.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Concatenate the segment files, in order, into a fresh `tmp_name` and
+/// delete them. Returns the size of the resulting `.part`.
+pub async fn merge_segments(tmp_name: &Path, segment_count: usize) -> Result<u64, DlmError> {
+    let mut part = tfs::File::create(tmp_name).await?;
+    for index in 0..segment_count {
+        let mut segment = tfs::File::open(segment_path(tmp_name, index)).await?;
+        tokio::io::copy(&mut segment, &mut part).await?;
+    }
+    part.flush().await?;
+    part.sync_all().await?;
+    let size = part.metadata().await?.len();
+    for index in 0..segment_count {
+        tfs::remove_file(segment_path(tmp_name, index)).await?;
+    }
+    Ok(size)
+}
+
+/// Delete the segment files left over for `tmp_name`, if any.
+pub async fn remove_segments(tmp_name: &Path, segment_count: usize) -> Result<(), DlmError> {
+    for index in 0..segment_count {
+        match tfs::remove_file(segment_path(tmp_name, index)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod segment_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn split_even() {
+        assert_eq!(
+            split_ranges(100, 4),
+            vec![(0, 24), (25, 49), (50, 74), (75, 99)]
+        );
+    }
+
+    #[test]
+    fn split_spreads_remainder_over_first_ranges() {
+        assert_eq!(split_ranges(10, 3), vec![(0, 3), (4, 6), (7, 9)]);
+    }
+
+    #[test]
+    fn split_never_yields_empty_ranges() {
+        assert_eq!(split_ranges(2, 8), vec![(0, 0), (1, 1)]);
+        assert!(split_ranges(0, 4).is_empty());
+    }
+
+    #[test]
+    fn split_single_range_covers_everything() {
+        assert_eq!(split_ranges(1234, 1), vec![(0, 1233)]);
+    }
+
+    #[test]
+    fn segment_path_appends_index() {
+        let path = segment_path(Path::new("/tmp/file.bin.part"), 3);
+        assert_eq!(path, PathBuf::from("/tmp/file.bin.part.3"));
+    }
+
+    #[tokio::test]
+    async fn merge_concatenates_in_order_and_removes_segments() {
+        let dir = tempdir().unwrap();
+        let tmp_name = dir.path().join("file.bin.part");
+        std::fs::write(segment_path(&tmp_name, 0), b"hello ").unwrap();
+        std::fs::write(segment_path(&tmp_name, 1), b"segmented ").unwrap();
+        std::fs::write(segment_path(&tmp_name, 2), b"world").unwrap();
+
+        let size = merge_segments(&tmp_name, 3).await.unwrap();
+
+        assert_eq!(size, 21);
+        assert_eq!(std::fs::read(&tmp_name).unwrap(), b"hello segmented world");
+        for index in 0..3 {
+            assert!(!segment_path(&tmp_name, index).exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn remove_segments_ignores_missing_ones() {
+        let dir = tempdir().unwrap();
+        let tmp_name = dir.path().join("file.bin.part");
+        std::fs::write(segment_path(&tmp_name, 1), b"left over").unwrap();
+
+        remove_segments(&tmp_name, 3).await.unwrap();
+
+        assert!(!segment_path(&tmp_name, 1).exists());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::dlm_error::DlmError;
+use crate::headers::{Metadata, if_range_validator};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs as tfs;
+
+/// Record of where a `.part` file comes from, kept next to it while the
+/// download is unfinished. It lets a later run check that the `.part` still
+/// matches the remote file before resuming it, and shows what a stuck
+/// download was doing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sidecar {
+    /// URL the `.part` was last filled from.
+    pub url: String,
+    /// Total size of the file, if announced by the server.
+    pub content_length: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Inclusive byte ranges of a segmented download, one segment file each.
+    pub segments: Vec<(u64, u64)>,
+    /// RFC 3339 timestamps.
+    pub started_at: String,
+    pub updated_at: String,
+}
+
+impl Sidecar {
+    pub fn new(url: &str, content_length: Option<u64>, metadata: &Metadata) -> Self {
+        let now = Timestamp::now().to_string();
+        Self {
+            url: url.to_string(),
+            content_length,
+            etag: metadata.etag.clone(),
+            last_modified: metadata.last_modified.clone(),
+            segments: Vec::new(),
+            started_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// On-disk location of the sidecar of the `.part` file `tmp_name`,
+    /// e.g. `file.bin.part.dlm.json`.
+    pub fn path(tmp_name: &Path) -> PathBuf {
+        let mut name = tmp_name.as_os_str().to_owned();
+        name.push(".dlm.json");
+        PathBuf::from(name)
+    }
+
+    /// Sidecar of `tmp_name`, if any. An unreadable sidecar counts as
+    /// missing: the `.part` is then resumed on its size alone.
+    pub async fn load(tmp_name: &Path) -> Option<Self> {
+        let content = tfs::read(Self::path(tmp_name)).await.ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub async fn save(&mut self, tmp_name: &Path) -> Result<(), DlmError> {
+        self.updated_at = Timestamp::now().to_string();
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| DlmError::other(format!("could not serialize sidecar - {e}")))?;
+        tfs::write(Self::path(tmp_name), content).await?;
+        Ok(())
+    }
+
+    pub async fn remove(tmp_name: &Path) -> Result<(), DlmError> {
+        match tfs::remove_file(Self::path(tmp_name)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keep the sidecar of a completed `.part` as the record of the final
+    /// file, e.g. `file.bin.dlm.json`, so `--update` can send its `ETag`.
+    pub async fn keep_for(tmp_name: &Path, final_file_path: &Path) -> Result<(), DlmError> {
+        match tfs::rename(Self::path(tmp_name), Self::path(final_file_path)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Validator of the version of the file held by the `.part`.
+    pub fn validator(&self) -> Option<&str> {
+        if_range_validator(self.etag.as_deref(), self.last_modified.as_deref())
+    }
+
+    /// Whether the remote file, as described now by the server, is another
+    /// version than the one the `.part` was started from. Unknown values on
+    /// either side are not taken as a change.
+    pub fn is_outdated(&self, content_length: Option<u64>, metadata: &Metadata) -> bool {
+        let differ = |stored: Option<&str>, current: Option<&str>| matches!((stored, current), (Some(s), Some(c)) if s != c);
+        differ(self.validator(), metadata.validator())
+            || matches!((self.content_length, content_length), (Some(s), Some(c)) if s != c)
+    }
+
+    /// Whether the file this sidecar was kept for is the version the server
+    /// describes now: the ETag decides, else the Last-Modified date, and a
+    /// different size is always a change. `None` when nothing tells.
+    pub fn is_current(&self, content_length: Option<u64>, metadata: &Metadata) -> Option<bool> {
+        let same = |stored: Option<&str>, current: Option<&str>| Some(stored? == current?);
+        let version = same(self.etag.as_deref(), metadata.etag.as_deref()).or_else(|| {
+            same(
+                self.last_modified.as_deref(),
+                metadata.last_modified.as_deref(),
+            )
+        });
+        match (self.content_length, content_length) {
+            (Some(stored), Some(current)) if stored != current => Some(false),
+            _ => version,
+        }
+    }
+
+    /// Replace the validators after the server started over with a new body.
+    pub fn reset_validators(&mut self, metadata: &Metadata) {
+        self.etag.clone_from(&metadata.etag);
+        self.last_modified.clone_from(&metadata.last_modified);
+    }
+}
+
+#[cfg(test)]
+mod sidecar_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn metadata(etag: Option<&str>) -> Metadata {
+        Metadata {
+            etag: etag.map(ToString::to_string),
+            ..Metadata::default()
+        }
+    }
+
+    #[test]
+    fn path_appends_suffix() {
+        let path = Sidecar::path(Path::new("/tmp/file.bin.part"));
+        assert_eq!(path, PathBuf::from("/tmp/file.bin.part.dlm.json"));
+    }
+
+    #[tokio::test]
+    async fn save_load_and_remove() {
+        let dir = tempdir().unwrap();
+        let part = dir.path().join("file.bin.part");
+        assert_eq!(Sidecar::load(&part).await, None);
+
+        let mut sidecar = Sidecar::new(
+            "https://e.com/file.bin",
+            Some(10),
+            &metadata(Some("\"v1\"")),
+        );
+        sidecar.segments = vec![(0, 4), (5, 9)];
+        sidecar.save(&part).await.unwrap();
+        assert_eq!(Sidecar::load(&part).await, Some(sidecar));
+
+        Sidecar::remove(&part).await.unwrap();
+        assert_eq!(Sidecar::load(&part).await, None);
+        // removing twice is fine
+        Sidecar::remove(&part).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupt_sidecar_is_ignored() {
+        let dir = tempdir().unwrap();
+        let part = dir.path().join("file.bin.part");
+        std::fs::write(Sidecar::path(&part), "{not json").unwrap();
+        assert_eq!(Sidecar::load(&part).await, None);
+    }
+
+    #[test]
+    fn outdated_on_validator_or_length_change() {
+        let sidecar = Sidecar::new("https://e.com/f", Some(10), &metadata(Some("\"v1\"")));
+        assert!(!sidecar.is_outdated(Some(10), &metadata(Some("\"v1\""))));
+        assert!(sidecar.is_outdated(Some(10), &metadata(Some("\"v2\""))));
+        assert!(sidecar.is_outdated(Some(11), &metadata(Some("\"v1\""))));
+        // nothing to compare with
+        assert!(!sidecar.is_outdated(None, &metadata(None)));
+    }
+
+    #[test]
+    fn current_only_when_a_validator_matches() {
+        let sidecar = Sidecar::new("https://e.com/f", Some(10), &metadata(Some("\"v1\"")));
+        assert_eq!(
+            sidecar.is_current(Some(10), &metadata(Some("\"v1\""))),
+            Some(true)
+        );
+        assert_eq!(
+            sidecar.is_current(Some(10), &metadata(Some("\"v2\""))),
+            Some(false)
+        );
+        assert_eq!(
+            sidecar.is_current(Some(11), &metadata(Some("\"v1\""))),
+            Some(false)
+        );
+        // the same size alone does not make it the same file
+        assert_eq!(sidecar.is_current(Some(10), &metadata(None)), None);
+    }
+}
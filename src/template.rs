@@ -0,0 +1,218 @@
+use crate::dlm_error::DlmError;
+use crate::file_link::cleanup_filename;
+use jiff::civil::Date;
+use std::path::PathBuf;
+
+/// Destination of each download relative to the output directory, e.g.
+/// `{host}/{yyyy}-{mm}/{name}.{ext}` or `{index:05}_{filename}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+    /// `{index}` zero-padded to `width` digits.
+    Index {
+        width: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Host,
+    Filename,
+    Name,
+    Ext,
+    Year,
+    Month,
+    Day,
+}
+
+/// What a template is expanded with for one download.
+pub struct TemplateValues<'a> {
+    pub host: &'a str,
+    /// File name without its extension.
+    pub name: &'a str,
+    pub ext: Option<&'a str>,
+    /// Position of the entry in the input, e.g. its line number.
+    pub index: usize,
+    /// Last modification date of the remote file, or the download date.
+    pub date: Date,
+}
+
+impl OutputTemplate {
+    pub fn parse(template: &str) -> Result<Self, DlmError> {
+        let error = |message: String| DlmError::CliArgumentError {
+            message: format!("invalid output template '{template}' - {message}"),
+        };
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| error("unclosed '{'".to_string()))?;
+            let placeholder = &rest[start + 1..start + end];
+            parts.push(
+                Self::parse_placeholder(placeholder)
+                    .ok_or_else(|| error(format!("unknown placeholder '{{{placeholder}}}'")))?,
+            );
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    fn parse_placeholder(placeholder: &str) -> Option<Part> {
+        let placeholder = match placeholder {
+            "host" => Placeholder::Host,
+            "filename" => Placeholder::Filename,
+            "name" => Placeholder::Name,
+            "ext" => Placeholder::Ext,
+            "yyyy" => Placeholder::Year,
+            "mm" => Placeholder::Month,
+            "dd" => Placeholder::Day,
+            "index" => return Some(Part::Index { width: 0 }),
+            _ => {
+                let width = placeholder.strip_prefix("index:")?;
+                if width.is_empty() || !width.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                return Some(Part::Index {
+                    width: width.parse().ok()?,
+                });
+            }
+        };
+        Some(Part::Placeholder(placeholder))
+    }
+
+    /// Relative path of the download. Each `/`-separated component is
+    /// sanitized like a file name and `.` or `..` components are dropped, so
+    /// the path stays under the output directory.
+    pub fn expand(&self, values: &TemplateValues<'_>) -> Result<PathBuf, DlmError> {
+        let mut expanded = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => expanded.push_str(literal),
+                Part::Index { width } => {
+                    expanded.push_str(&format!("{:0width$}", values.index));
+                }
+                Part::Placeholder(placeholder) => match placeholder {
+                    Placeholder::Host => expanded.push_str(values.host),
+                    Placeholder::Filename => {
+                        expanded.push_str(values.name);
+                        if let Some(ext) = values.ext {
+                            expanded.push('.');
+                            expanded.push_str(ext);
+                        }
+                    }
+                    Placeholder::Name => expanded.push_str(values.name),
+                    Placeholder::Ext => expanded.push_str(values.ext.unwrap_or_default()),
+                    Placeholder::Year => expanded.push_str(&format!("{:04}", values.date.year())),
+                    Placeholder::Month => {
+                        expanded.push_str(&format!("{:02}", values.date.month()));
+                    }
+                    Placeholder::Day => expanded.push_str(&format!("{:02}", values.date.day())),
+                },
+            }
+        }
+
+        let components: Vec<String> = expanded
+            .split(['/', '\\'])
+            .map(cleanup_filename)
+            .filter(|c| !c.is_empty() && c != "." && c != "..")
+            .collect();
+        if components.is_empty() || expanded.ends_with(['/', '\\']) {
+            return Err(DlmError::other(format!(
+                "output template expanded to '{expanded}' which has no file name"
+            )));
+        }
+        Ok(components.iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            host: "example.com",
+            name: "archive.tar",
+            ext: Some("gz"),
+            index: 7,
+            date: Date::new(2024, 3, 9).unwrap(),
+        }
+    }
+
+    fn expand(template: &str) -> PathBuf {
+        OutputTemplate::parse(template)
+            .unwrap()
+            .expand(&values())
+            .unwrap()
+    }
+
+    #[test]
+    fn expands_host_date_and_name() {
+        assert_eq!(
+            expand("{host}/{yyyy}-{mm}/{name}.{ext}"),
+            PathBuf::from("example.com/2024-03/archive.tar.gz")
+        );
+        assert_eq!(
+            expand("{dd}_{filename}"),
+            PathBuf::from("09_archive.tar.gz")
+        );
+    }
+
+    #[test]
+    fn pads_index() {
+        assert_eq!(
+            expand("{index:05}_{filename}"),
+            PathBuf::from("00007_archive.tar.gz")
+        );
+        assert_eq!(expand("{index}-{name}"), PathBuf::from("7-archive.tar"));
+    }
+
+    #[test]
+    fn missing_extension_leaves_no_trailing_dot() {
+        let values = TemplateValues {
+            ext: None,
+            ..values()
+        };
+        let template = OutputTemplate::parse("{name}.{ext}").unwrap();
+        assert_eq!(
+            template.expand(&values).unwrap(),
+            PathBuf::from("archive.tar")
+        );
+    }
+
+    #[test]
+    fn components_are_sanitized_and_cannot_escape() {
+        assert_eq!(
+            expand("../{host}/./a:b/{filename}"),
+            PathBuf::from("example.com/a_b/archive.tar.gz")
+        );
+    }
+
+    #[test]
+    fn template_without_file_name_is_an_error() {
+        let template = OutputTemplate::parse("{host}/").unwrap();
+        assert!(template.expand(&values()).is_err());
+        let template = OutputTemplate::parse("..").unwrap();
+        assert!(template.expand(&values()).is_err());
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert!(OutputTemplate::parse("{nope}").is_err());
+        assert!(OutputTemplate::parse("{index:x5}").is_err());
+        assert!(OutputTemplate::parse("{name").is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::dlm_error::DlmError;
+use crate::download_entry::DownloadEntry;
+
+/// curl-style URL pattern, e.g. `https://host/part[001-250].bin` or
+/// `https://{eu,us}.host/img_[a-z:2].jpg`, standing for all the URLs it
+/// expands to. `\[`, `\]`, `\{` and `\}` are literal brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlPattern {
+    parts: Vec<Part>,
+    /// Number of URLs the pattern expands to.
+    len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// `{a,b,c}`
+    Set(Vec<String>),
+    /// `[1-100]`, `[001-100:5]` or `{001..100}`, zero-padded to `width` digits.
+    Numbers {
+        start: u64,
+        end: u64,
+        step: u64,
+        width: usize,
+    },
+    /// `[a-z]`, `[A-Z:2]` or `{a..z}`.
+    Letters {
+        start: u8,
+        end: u8,
+        step: u8,
+    },
+}
+
+impl Part {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Literal(_) => 1,
+            Self::Set(values) => values.len() as u64,
+            Self::Numbers {
+                start, end, step, ..
+            } => (end - start) / step + 1,
+            Self::Letters { start, end, step } => u64::from((end - start) / step + 1),
+        }
+    }
+
+    fn push_value(&self, position: u64, url: &mut String) {
+        match self {
+            Self::Literal(literal) => url.push_str(literal),
+            Self::Set(values) => url.push_str(&values[position as usize]),
+            Self::Numbers {
+                start, step, width, ..
+            } => url.push_str(&format!("{:0width$}", start + position * step)),
+            Self::Letters { start, step, .. } => {
+                url.push(char::from(start + position as u8 * step));
+            }
+        }
+    }
+}
+
+impl UrlPattern {
+    pub fn parse(url: &str) -> Result<Self, DlmError> {
+        let error =
+            |message: &str| DlmError::other(format!("invalid URL pattern '{url}' - {message}"));
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = url.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.clone().next() {
+                    Some(escaped @ ('[' | ']' | '{' | '}')) => {
+                        chars.next();
+                        literal.push(escaped);
+                    }
+                    _ => literal.push(c),
+                },
+                '[' | '{' => {
+                    let close = if c == '[' { ']' } else { '}' };
+                    let mut body = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(next) if next == close => break,
+                            Some('[' | '{') => return Err(error("nested patterns")),
+                            Some(next) => body.push(next),
+                            None => return Err(error(&format!("unclosed '{c}'"))),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    let part = if c == '[' {
+                        Self::parse_range(&body, "-", ":")
+                    } else if body.contains(',') {
+                        Some(Part::Set(body.split(',').map(str::to_string).collect()))
+                    } else {
+                        Self::parse_range(&body, "..", "..")
+                            .or_else(|| Some(Part::Set(vec![body.clone()])))
+                    };
+                    parts.push(part.ok_or_else(|| error(&format!("invalid range '[{body}]'")))?);
+                }
+                ']' | '}' => return Err(error(&format!("unmatched '{c}'"))),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        let len = parts
+            .iter()
+            .try_fold(1u64, |len, part| len.checked_mul(part.len()))
+            .ok_or_else(|| error("too many URLs"))?;
+        Ok(Self { parts, len })
+    }
+
+    /// `start-end` or `start-end:step` with the given separators, e.g.
+    /// `001-100:5`; `None` if the body is not a valid range.
+    fn parse_range(body: &str, to: &str, step_separator: &str) -> Option<Part> {
+        let (start, rest) = body.split_once(to)?;
+        let (end, step) = match rest.split_once(step_separator) {
+            Some((end, step)) => (end, Some(step)),
+            None => (rest, None),
+        };
+        let step: u64 = match step {
+            Some(step) => step.parse().ok().filter(|step| *step > 0)?,
+            None => 1,
+        };
+        let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if is_number(start) && is_number(end) {
+            let width = if start.len() > 1 && start.starts_with('0') {
+                start.len()
+            } else {
+                0
+            };
+            let (start, end): (u64, u64) = (start.parse().ok()?, end.parse().ok()?);
+            // the number of values must fit in a u64
+            return (start <= end && (end - start) / step < u64::MAX).then_some(Part::Numbers {
+                start,
+                end,
+                step,
+                width,
+            });
+        }
+        match (start.as_bytes(), end.as_bytes()) {
+            ([start], [end])
+                if start.is_ascii_alphabetic() && end.is_ascii_alphabetic() && start <= end =>
+            {
+                Some(Part::Letters {
+                    start: *start,
+                    end: *end,
+                    step: u8::try_from(step).ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of URLs the pattern expands to.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The URLs of the pattern, the last range changing fastest.
+    pub fn into_urls(self) -> impl Iterator<Item = String> + Send + 'static {
+        (0..self.len).map(move |index| self.url_at(index))
+    }
+
+    fn url_at(&self, mut index: u64) -> String {
+        let mut positions = vec![0; self.parts.len()];
+        for (position, part) in positions.iter_mut().zip(&self.parts).rev() {
+            *position = index % part.len();
+            index /= part.len();
+        }
+        let mut url = String::new();
+        for (part, position) in self.parts.iter().zip(positions) {
+            part.push_value(position, &mut url);
+        }
+        url
+    }
+}
+
+/// One entry per URL of the pattern of `entry`, with its other settings.
+/// A filename or checksum only fits a single file, the entry is rejected if
+/// it has one and expands to several URLs.
+pub fn expand_entry(
+    entry: DownloadEntry,
+) -> Result<impl Iterator<Item = DownloadEntry> + Send + 'static, DlmError> {
+    let pattern = UrlPattern::parse(&entry.url)?;
+    if pattern.len() > 1 {
+        let setting = if entry.filename.is_some() {
+            Some("filename")
+        } else if entry.checksum.is_some() {
+            Some("checksum")
+        } else {
+            None
+        };
+        if let Some(setting) = setting {
+            return Err(DlmError::other(format!(
+                "a {setting} cannot apply to the several files of a URL pattern"
+            )));
+        }
+    }
+    let first_index = entry.index;
+    Ok(pattern
+        .into_urls()
+        .zip(first_index..)
+        .map(move |(url, index)| DownloadEntry {
+            url,
+            index,
+            ..entry.clone()
+        }))
+}
+
+/// Number of URLs `url` expands to, 1 for an invalid pattern that is
+/// reported once.
+pub fn count_urls(url: &str) -> u64 {
+    UrlPattern::parse(url).map_or(1, |pattern| pattern.len())
+}
+
+#[cfg(test)]
+mod url_pattern_tests {
+    use super::*;
+    use crate::checksum::Checksum;
+
+    fn expand(url: &str) -> Vec<String> {
+        UrlPattern::parse(url).unwrap().into_urls().collect()
+    }
+
+    #[test]
+    fn plain_url_is_itself() {
+        assert_eq!(
+            expand("https://example.com/a.bin?x=1"),
+            ["https://example.com/a.bin?x=1"]
+        );
+    }
+
+    #[test]
+    fn set_and_padded_range() {
+        assert_eq!(
+            expand("https://{eu,us}.example.com/part[008-010].bin"),
+            [
+                "https://eu.example.com/part008.bin",
+                "https://eu.example.com/part009.bin",
+                "https://eu.example.com/part010.bin",
+                "https://us.example.com/part008.bin",
+                "https://us.example.com/part009.bin",
+                "https://us.example.com/part010.bin",
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_with_step() {
+        assert_eq!(
+            expand("https://example.com/[0-10:5]"),
+            [
+                "https://example.com/0",
+                "https://example.com/5",
+                "https://example.com/10"
+            ]
+        );
+        assert_eq!(
+            expand("https://example.com/img_[a-e:2].jpg"),
+            [
+                "https://example.com/img_a.jpg",
+                "https://example.com/img_c.jpg",
+                "https://example.com/img_e.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn brace_ranges() {
+        assert_eq!(
+            expand("https://example.com/part{01..3}.bin"),
+            [
+                "https://example.com/part01.bin",
+                "https://example.com/part02.bin",
+                "https://example.com/part03.bin"
+            ]
+        );
+        assert_eq!(
+            expand("https://example.com/{x..z..2}"),
+            ["https://example.com/x", "https://example.com/z"]
+        );
+    }
+
+    #[test]
+    fn escaped_brackets_are_literal() {
+        assert_eq!(
+            expand(r"https://example.com/?a\[\]=1"),
+            ["https://example.com/?a[]=1"]
+        );
+    }
+
+    #[test]
+    fn len_is_the_product_of_the_ranges() {
+        let pattern = UrlPattern::parse("https://example.com/{a,b,c}/[1-250].bin").unwrap();
+        assert_eq!(pattern.len(), 750);
+        assert_eq!(count_urls("https://example.com/[1-"), 1);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for url in [
+            "https://example.com/[1-",
+            "https://example.com/[5-1]",
+            "https://example.com/[1-5:0]",
+            "https://example.com/[a-5]",
+            "https://example.com/{a,[1-2]}",
+            "https://example.com/a]",
+            "https://example.com/[0-18446744073709551615][0-1]",
+        ] {
+            assert!(UrlPattern::parse(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn expanded_entries_keep_their_settings() {
+        let entry = DownloadEntry {
+            dir: Some("parts".into()),
+            ..DownloadEntry::new("https://example.com/part[1-2].bin")
+        };
+        let entries: Vec<_> = expand_entry(entry).unwrap().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].url, "https://example.com/part2.bin");
+        assert_eq!(entries[1].dir, Some("parts".into()));
+    }
+
+    #[test]
+    fn expanded_entries_are_numbered_from_the_pattern_index() {
+        let entry = DownloadEntry {
+            index: 4,
+            ..DownloadEntry::new("https://example.com/part[1-3].bin")
+        };
+        let indexes: Vec<_> = expand_entry(entry).unwrap().map(|e| e.index).collect();
+        assert_eq!(indexes, [4, 5, 6]);
+    }
+
+    #[test]
+    fn filename_and_checksum_need_a_single_url() {
+        let named = DownloadEntry {
+            filename: Some("x.bin".into()),
+            ..DownloadEntry::new("https://example.com/part[1-5].bin")
+        };
+        let error = expand_entry(named.clone()).err().unwrap();
+        assert!(
+            error.to_string().contains("a filename cannot apply"),
+            "{error}"
+        );
+        let checked = DownloadEntry {
+            checksum: Some(Checksum::parse(&format!("sha256={}", "0".repeat(64))).unwrap()),
+            ..DownloadEntry::new("https://example.com/part[1-5].bin")
+        };
+        let error = expand_entry(checked).err().unwrap();
+        assert!(
+            error.to_string().contains("a checksum cannot apply"),
+            "{error}"
+        );
+        // a pattern standing for one URL is just that URL
+        let single = DownloadEntry {
+            url: "https://example.com/part[1-1].bin".into(),
+            ..named
+        };
+        let entries: Vec<_> = expand_entry(single).unwrap().collect();
+        assert_eq!(entries[0].filename.as_deref(), Some("x.bin"));
+    }
+}
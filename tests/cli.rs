@@ -11,7 +11,8 @@
 
 mod common;
 
-use common::{FILE_BODY, TestServer};
+use common::{CURRENT_ETAG, FILE_BODY, TestServer, file_body_sha256};
+use std::io::Write;
 use std::path::Path;
 use std::time::Duration;
 use tempfile::TempDir;
@@ -711,3 +712,1808 @@ async fn slow_first_byte_is_tolerated() {
     );
     assert_eq!(read(&dir.path().join("data.bin")), FILE_BODY);
 }
+
+#[tokio::test]
+async fn segmented_download() {
+    // The body is split into 4 ranges fetched concurrently, then stitched
+    // back together — any ordering bug shows up as a content mismatch.
+    let server = TestServer::start().await;
+    let url = server.url("/file/segmented.bin");
+
+    let (r, dir) = run_dlm(&[&url, "--segments", "4"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&dir.path().join("segmented.bin")), FILE_BODY);
+    let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|n| n != "segmented.bin")
+        .collect();
+    assert!(
+        leftovers.is_empty(),
+        "segment files left behind: {leftovers:?}"
+    );
+}
+
+#[tokio::test]
+async fn segmented_download_resumes_interrupted_segments() {
+    // Pre-seed the segments of a prior interrupted run: the first is complete,
+    // the second is half done, the others are missing.
+    let server = TestServer::start().await;
+    let url = server.url("/file/seg-resume.bin");
+    let tmp = TempDir::new().unwrap();
+    let quarter = FILE_BODY.len() / 4;
+    std::fs::write(
+        tmp.path().join("seg-resume.bin.part.0"),
+        &FILE_BODY[..quarter],
+    )
+    .unwrap();
+    std::fs::write(
+        tmp.path().join("seg-resume.bin.part.1"),
+        &FILE_BODY[quarter..quarter + quarter / 2],
+    )
+    .unwrap();
+
+    let r = run_dlm_in(&[&url, "--segments", "4", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("seg-resume.bin")), FILE_BODY);
+    assert!(!tmp.path().join("seg-resume.bin.part.1").exists());
+}
+
+#[tokio::test]
+async fn single_stream_download_removes_leftover_segments() {
+    // A previous run split the file in two, this one downloads it whole.
+    let server = TestServer::start().await;
+    let url = server.url("/file/regrouped.bin");
+    let tmp = TempDir::new().unwrap();
+    let part = tmp.path().join("regrouped.bin.part");
+    let half = FILE_BODY.len() as u64 / 2;
+    write_sidecar_with_segments(&part, &[(0, half - 1), (half, FILE_BODY.len() as u64 - 1)]);
+    std::fs::write(tmp.path().join("regrouped.bin.part.0"), &FILE_BODY[..10]).unwrap();
+    std::fs::write(tmp.path().join("regrouped.bin.part.1"), &FILE_BODY[..10]).unwrap();
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("regrouped.bin")), FILE_BODY);
+    assert!(!tmp.path().join("regrouped.bin.part.0").exists());
+    assert!(!tmp.path().join("regrouped.bin.part.1").exists());
+}
+
+#[tokio::test]
+async fn segmented_download_falls_back_without_range_support() {
+    let server = TestServer::start().await;
+    let url = server.url("/no-range/single.bin");
+
+    let (r, dir) = run_dlm(&[&url, "--segments", "4"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&dir.path().join("single.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn max_bandwidth_throttles_download() {
+    // 64 KiB at 32 KiB/s: the first second worth of tokens is a free burst,
+    // the remaining 32 KiB take about one more second.
+    let server = TestServer::start().await;
+    let url = server.url("/file/throttled.bin");
+
+    let start = std::time::Instant::now();
+    let (r, dir) = no_hang(run_dlm(&[&url, "--max-bandwidth", "32K"])).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&dir.path().join("throttled.bin")), FILE_BODY);
+    assert!(
+        start.elapsed() >= Duration::from_millis(800),
+        "download finished too fast for the bandwidth cap: {:?}",
+        start.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn max_bandwidth_per_download_throttles_segments_together() {
+    // The per-download cap is shared by all segments of the same file.
+    let server = TestServer::start().await;
+    let url = server.url("/file/throttled.bin");
+
+    let start = std::time::Instant::now();
+    let (r, dir) = no_hang(run_dlm(&[
+        &url,
+        "--segments",
+        "4",
+        "--max-bandwidth-per-download",
+        "32K",
+    ]))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&dir.path().join("throttled.bin")), FILE_BODY);
+    assert!(
+        start.elapsed() >= Duration::from_millis(800),
+        "download finished too fast for the bandwidth cap: {:?}",
+        start.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn max_bandwidth_rejects_invalid_value() {
+    let r = run_dlm_raw(&["http://example.invalid/foo.bin", "--max-bandwidth", "fast"]).await;
+
+    assert_ne!(r.code, 0, "should exit non-zero on a bad rate: {r}");
+    assert!(r.stderr.contains("max-bandwidth"), "{r}");
+}
+
+#[tokio::test]
+async fn max_concurrent_per_domain_limits_each_host() {
+    // `127.0.0.1` and `localhost` reach the same server under two different
+    // hosts. With one slot per host, the server sees at most 2 GETs at once
+    // even though 4 global slots are available.
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = tmp.path().join("links.txt");
+    let ip_url = |name: &str| server.url(&format!("/slow/{name}"));
+    let localhost_url = |name: &str| ip_url(name).replace("127.0.0.1", "localhost");
+    std::fs::write(
+        &input,
+        format!(
+            "{}\n{}\n{}\n{}\n",
+            ip_url("a.bin"),
+            ip_url("b.bin"),
+            localhost_url("c.bin"),
+            localhost_url("d.bin"),
+        ),
+    )
+    .unwrap();
+
+    let r = no_hang(run_dlm_in(
+        &[
+            "-i",
+            input.to_str().unwrap(),
+            "--max-concurrent",
+            "4",
+            "--max-concurrent-per-domain",
+            "1",
+        ],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in ["a.bin", "b.bin", "c.bin", "d.bin"] {
+        assert_eq!(read(&tmp.path().join(name)), FILE_BODY);
+    }
+    assert_eq!(server.max_in_flight(), 2, "{r}");
+}
+
+#[tokio::test]
+async fn interleave_domains_downloads_every_link() {
+    // Reordering must keep every link (and still skip comments and blanks).
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = tmp.path().join("links.txt");
+    let localhost = |path: &str| server.url(path).replace("127.0.0.1", "localhost");
+    std::fs::write(
+        &input,
+        format!(
+            "# same host first\n{}\n{}\n\n{}\n",
+            server.url("/file/one.bin"),
+            server.url("/file/two.bin"),
+            localhost("/file/three.bin"),
+        ),
+    )
+    .unwrap();
+
+    let r = run_dlm_in(
+        &["-i", input.to_str().unwrap(), "--interleave-domains"],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in ["one.bin", "two.bin", "three.bin"] {
+        assert_eq!(read(&tmp.path().join(name)), FILE_BODY);
+    }
+}
+
+#[tokio::test]
+async fn interleave_domains_requires_input_file() {
+    let r = run_dlm_raw(&["http://example.invalid/foo.bin", "--interleave-domains"]).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("input-file"), "{r}");
+}
+
+/// Poll until `path` exists, for tests driving a long-running dlm.
+async fn wait_for_file(path: &Path) {
+    no_hang(async {
+        while !path.exists() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn input_file_dash_reads_stdin() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_dlm"));
+    cmd.args(["-i", "-", "-o"])
+        .arg(tmp.path())
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    let mut child = cmd.spawn().expect("failed to spawn dlm");
+    let input = format!(
+        "{}\n  out=renamed.bin\n{}\n",
+        server.url("/file/one.bin"),
+        server.url("/file/two.bin")
+    );
+    let mut stdin = child.stdin.take().unwrap();
+    tokio::io::AsyncWriteExt::write_all(&mut stdin, input.as_bytes())
+        .await
+        .unwrap();
+    // closing stdin ends the input
+    drop(stdin);
+
+    let output = no_hang(child.wait_with_output()).await.unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert_eq!(read(&tmp.path().join("renamed.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("two.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn follow_downloads_appended_urls() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(tmp.path(), &format!("{}\n", server.url("/file/one.bin")));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dlm"))
+        .args(["-i", &input, "--follow", "-o"])
+        .arg(tmp.path())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .expect("failed to spawn dlm");
+
+    // the last URL is downloaded without waiting for the next line
+    wait_for_file(&tmp.path().join("one.bin")).await;
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&input)
+        .unwrap();
+    // a line is only read once complete
+    write!(file, "{}", server.url("/file/two.bin")).unwrap();
+    file.flush().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    writeln!(file, "\n{}", server.url("/file/three.bin")).unwrap();
+    file.flush().unwrap();
+    wait_for_file(&tmp.path().join("three.bin")).await;
+    wait_for_file(&tmp.path().join("two.bin")).await;
+
+    // still running, waiting for more URLs
+    assert!(child.try_wait().unwrap().is_none());
+    child.kill().await.unwrap();
+    assert_eq!(read(&tmp.path().join("one.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn glob_expands_the_url() {
+    let server = TestServer::start().await;
+    let url = server.url("/file/part[08-10].bin");
+
+    let (r, tmp) = run_dlm(&[&url, "--glob"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in ["part08.bin", "part09.bin", "part10.bin"] {
+        assert_eq!(read(&tmp.path().join(name)), FILE_BODY);
+    }
+}
+
+#[tokio::test]
+async fn glob_expands_input_file_lines_with_their_options() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  dir=letters\n{}\n",
+            server.url("/file/{one,two}_[a-b].bin"),
+            server.url("/file/plain.bin"),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--glob"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in ["one_a.bin", "one_b.bin", "two_a.bin", "two_b.bin"] {
+        assert_eq!(read(&tmp.path().join("letters").join(name)), FILE_BODY);
+    }
+    assert_eq!(read(&tmp.path().join("plain.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn glob_rejects_output_for_several_files() {
+    let url = "http://example.invalid/part[1-2].bin";
+    let r = run_dlm_raw(&[url, "--glob", "-O", "part.bin"]).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("'--output' cannot name"), "{r}");
+
+    let r = run_dlm_raw(&["http://example.invalid/part[1-2.bin", "--glob"]).await;
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("invalid URL pattern"), "{r}");
+}
+
+#[tokio::test]
+async fn glob_rejects_input_file_filename_for_several_files() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  out=same.bin\n{}\n",
+            server.url("/file/part[1-3].bin"),
+            server.url("/file/plain.bin"),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--glob"], tmp.path()).await;
+
+    // the pattern is not downloaded at all, the other line is
+    assert!(!tmp.path().join("same.bin").exists(), "{r}");
+    assert!(!tmp.path().join("part1.bin").exists(), "{r}");
+    assert_eq!(read(&tmp.path().join("plain.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn follow_rejects_stdin() {
+    let r = run_dlm_raw(&["-i", "-", "--follow"]).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("'--follow' cannot be used"), "{r}");
+}
+
+#[tokio::test]
+async fn autopilot_starts_low_and_downloads_everything() {
+    // The autopilot starts with a single download and only opens more slots
+    // as downloads complete, so the server never sees all 4 at once even
+    // though `--max-concurrent` would allow it.
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = tmp.path().join("links.txt");
+    let names = ["a.bin", "b.bin", "c.bin", "d.bin"];
+    let links: Vec<String> = names
+        .iter()
+        .map(|name| server.url(&format!("/slow/{name}")))
+        .collect();
+    std::fs::write(&input, links.join("\n")).unwrap();
+
+    let r = no_hang(run_dlm_in(
+        &[
+            "-i",
+            input.to_str().unwrap(),
+            "--max-concurrent",
+            "4",
+            "--autopilot",
+        ],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in names {
+        assert_eq!(read(&tmp.path().join(name)), FILE_BODY);
+    }
+    assert!(server.max_in_flight() < 4, "{r}");
+}
+
+/// Write a single-line input file `links.txt` in `dir` and return its path.
+fn write_input(dir: &Path, content: &str) -> String {
+    let input = dir.join("links.txt");
+    std::fs::write(&input, content).unwrap();
+    input.to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn checksum_from_input_file_verified() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{} sha256={}\n",
+            server.url("/file/checked.bin"),
+            file_body_sha256()
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("checked.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_mismatch_leaves_part_file() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{} sha256={}\n",
+            server.url("/file/corrupt.bin"),
+            "0".repeat(64)
+        ),
+    );
+
+    let _r = run_dlm_in(&["-i", &input, "--retry", "0"], tmp.path()).await;
+
+    assert!(
+        !tmp.path().join("corrupt.bin").exists(),
+        "a body failing verification must not be finalized"
+    );
+    assert_eq!(read(&tmp.path().join("corrupt.bin.part")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_covers_resumed_prefix() {
+    // Half the body comes from a previous run: the digest must include it.
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    std::fs::write(
+        tmp.path().join("resumed.bin.part"),
+        &FILE_BODY[..FILE_BODY.len() / 2],
+    )
+    .unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{} sha256={}\n",
+            server.url("/file/resumed.bin"),
+            file_body_sha256()
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("resumed.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_verified_for_segmented_download() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{} sha256={}\n",
+            server.url("/file/segments.bin"),
+            file_body_sha256()
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--segments", "3"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("segments.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_manifest_verifies_listed_files() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let manifest = tmp.path().join("SHA256SUMS");
+    std::fs::write(
+        &manifest,
+        format!(
+            "{}  good.bin\n{} *./dist/bad.bin\n",
+            file_body_sha256(),
+            "0".repeat(64)
+        ),
+    )
+    .unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n{}\n{}\n",
+            server.url("/file/good.bin"),
+            server.url("/file/bad.bin"),
+            server.url("/file/unlisted.bin")
+        ),
+    );
+
+    let r = run_dlm_in(
+        &[
+            "-i",
+            &input,
+            "--checksums",
+            manifest.to_str().unwrap(),
+            "--retry",
+            "0",
+        ],
+        tmp.path(),
+    )
+    .await;
+
+    assert_ne!(r.code, 0, "a checksum mismatch must fail the run: {r}");
+    assert!(
+        r.stderr
+            .contains("checksum verification failed for 1 download(s)"),
+        "{r}"
+    );
+    assert!(r.stderr.contains(&server.url("/file/bad.bin")), "{r}");
+    assert_eq!(read(&tmp.path().join("good.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("unlisted.bin")), FILE_BODY);
+    assert!(!tmp.path().join("bad.bin").exists());
+    assert_eq!(read(&tmp.path().join("bad.bin.part")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_manifest_fetched_from_url() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+
+    let r = run_dlm_in(
+        &[
+            &server.url("/file/remote.bin"),
+            "--checksums",
+            &server.url("/sha256sums/remote.bin"),
+        ],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("remote.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn checksum_manifest_bsd_sha512_mismatch_fails() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let manifest = tmp.path().join("SHA512SUMS");
+    std::fs::write(
+        &manifest,
+        format!("SHA512 (remote.bin) = {}\n", "0".repeat(128)),
+    )
+    .unwrap();
+
+    let r = run_dlm_in(
+        &[
+            &server.url("/file/remote.bin"),
+            "--checksums",
+            manifest.to_str().unwrap(),
+            "--retry",
+            "0",
+        ],
+        tmp.path(),
+    )
+    .await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("remote.bin").exists());
+}
+
+#[tokio::test]
+async fn checksum_manifest_without_entries_is_rejected() {
+    let tmp = TempDir::new().unwrap();
+    let manifest = tmp.path().join("SUMS");
+    std::fs::write(&manifest, "not a manifest\n").unwrap();
+
+    let r = run_dlm_in(
+        &[
+            "http://example.invalid/a.bin",
+            "--checksums",
+            manifest.to_str().unwrap(),
+        ],
+        tmp.path(),
+    )
+    .await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("no supported checksum"), "{r}");
+}
+
+/// Write the sidecar of `part` as left by a previous run of the version `etag`.
+fn write_sidecar(part: &Path, etag: &str) {
+    let sidecar = serde_json::json!({
+        "url": "http://old.example/file",
+        "content_length": FILE_BODY.len(),
+        "etag": etag,
+        "last_modified": null,
+        "segments": [],
+        "started_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z",
+    });
+    let mut path = part.as_os_str().to_owned();
+    path.push(".dlm.json");
+    std::fs::write(path, sidecar.to_string()).unwrap();
+}
+
+/// Write the sidecar of `part` as left by a segmented run over `segments`,
+/// without validators.
+fn write_sidecar_with_segments(part: &Path, segments: &[(u64, u64)]) {
+    let sidecar = serde_json::json!({
+        "url": "http://old.example/file",
+        "content_length": FILE_BODY.len(),
+        "etag": null,
+        "last_modified": null,
+        "segments": segments,
+        "started_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z",
+    });
+    let mut path = part.as_os_str().to_owned();
+    path.push(".dlm.json");
+    std::fs::write(path, sidecar.to_string()).unwrap();
+}
+
+/// Write a Metalink 4 document listing `files` into `dir`.
+fn write_metalink(dir: &Path, files: &str) -> String {
+    let path = dir.join("files.meta4");
+    std::fs::write(
+        &path,
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">{files}</metalink>"#
+        ),
+    )
+    .unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn metalink_falls_back_to_next_mirror() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let metalink = write_metalink(
+        tmp.path(),
+        &format!(
+            r#"<file name="release.bin">
+  <size>{}</size>
+  <hash type="sha-256">{}</hash>
+  <url priority="2">{}</url>
+  <url priority="1">{}</url>
+</file>"#,
+            FILE_BODY.len(),
+            file_body_sha256(),
+            server.url("/file/mirror.bin"),
+            server.url("/unavailable/mirror.bin"),
+        ),
+    );
+
+    // every mirror is tried once even without retries
+    let r = run_dlm_in(&["--metalink", &metalink, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    // saved under the name declared by the Metalink, not the mirror's
+    assert_eq!(read(&tmp.path().join("release.bin")), FILE_BODY);
+    assert!(!tmp.path().join("mirror.bin").exists());
+}
+
+#[tokio::test]
+async fn size_mismatch_is_not_retried() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let entry = serde_json::json!({
+        "url": server.url("/counted/sized.bin"),
+        "size": FILE_BODY.len() + 1
+    });
+    let input = write_input(tmp.path(), &entry.to_string());
+
+    // default retries: the server will not announce another size
+    let r = no_hang(run_dlm_in(&["-i", &input], tmp.path())).await;
+
+    assert!(!tmp.path().join("sized.bin").exists(), "{r}");
+    // the HEAD announcing the size only
+    assert_eq!(server.counted_requests(), 1, "{r}");
+}
+
+#[tokio::test]
+async fn size_mismatch_falls_back_to_next_mirror() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let entry = serde_json::json!({
+        // announces the size of its own short body
+        "url": server.url("/echo-headers"),
+        "mirrors": [server.url("/file/mirror.bin")],
+        "filename": "release.bin",
+        "size": FILE_BODY.len()
+    });
+    let input = write_input(tmp.path(), &entry.to_string());
+
+    let r = run_dlm_in(&["-i", &input, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("release.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn metalink_size_mismatch_is_not_finalized() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let metalink = write_metalink(
+        tmp.path(),
+        &format!(
+            r#"<file name="sized.bin"><size>{}</size><url>{}</url></file>"#,
+            FILE_BODY.len() + 1,
+            server.url("/file/sized.bin"),
+        ),
+    );
+
+    let r = run_dlm_in(&["--metalink", &metalink, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("sized.bin").exists());
+}
+
+#[tokio::test]
+async fn metalink_hash_mismatch_fails_the_run() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let metalink = write_metalink(
+        tmp.path(),
+        &format!(
+            r#"<file name="hashed.bin"><hash type="sha-256">{}</hash><url>{}</url></file>"#,
+            "0".repeat(64),
+            server.url("/file/hashed.bin"),
+        ),
+    );
+
+    let r = run_dlm_in(&["--metalink", &metalink, "--retry", "0"], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("hashed.bin").exists());
+    assert_eq!(read(&tmp.path().join("hashed.bin.part")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn metalink_conflicts_with_input_file() {
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(tmp.path(), "http://example.invalid/a.bin\n");
+    let metalink = write_metalink(tmp.path(), "");
+
+    let r = run_dlm_in(&["-i", &input, "--metalink", &metalink], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+}
+
+#[tokio::test]
+async fn invalid_metalink_is_rejected() {
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("broken.meta4");
+    std::fs::write(&path, "<metalink>").unwrap();
+
+    let r = run_dlm_in(&["--metalink", path.to_str().unwrap()], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("invalid Metalink document"), "{r}");
+}
+
+#[tokio::test]
+async fn input_file_option_lines_apply_to_their_url() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  out=renamed.bin\n  dir=nested/deeper\n\n# comment between entries\n{}\n  header=X-Entry: one\n  referer=https://referer.example/page\n  out=echo.txt\n",
+            server.url("/file/original.bin"),
+            server.url("/echo-headers"),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "-m", "1"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(
+        read(&tmp.path().join("nested/deeper/renamed.bin")),
+        FILE_BODY
+    );
+    assert!(!tmp.path().join("original.bin").exists());
+    assert_eq!(read(&tmp.path().join("echo.txt")), b"echoed");
+    let headers = server.last_echo_headers();
+    assert_eq!(
+        headers.get("x-entry").and_then(|v| v.to_str().ok()),
+        Some("one")
+    );
+    assert_eq!(
+        headers.get("referer").and_then(|v| v.to_str().ok()),
+        Some("https://referer.example/page")
+    );
+}
+
+#[tokio::test]
+async fn input_file_dir_stays_under_the_output_dir() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let output_dir = tmp.path().join("out");
+    std::fs::create_dir(&output_dir).unwrap();
+    let absolute = tmp.path().join("absolute");
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  dir=../escaped\n{}\n  dir={}\n",
+            server.url("/file/relative.bin"),
+            server.url("/file/absolute.bin"),
+            absolute.display(),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input], &output_dir).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("escaped").exists());
+    assert!(!absolute.exists());
+    assert_eq!(read(&output_dir.join("escaped/relative.bin")), FILE_BODY);
+    let nested = absolute.strip_prefix("/").unwrap().join("absolute.bin");
+    assert_eq!(read(&output_dir.join(nested)), FILE_BODY);
+}
+
+#[tokio::test]
+async fn json_input_dir_stays_under_the_output_dir() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let output_dir = tmp.path().join("out");
+    std::fs::create_dir(&output_dir).unwrap();
+    let entries = serde_json::json!([
+        {"url": server.url("/file/relative.bin"), "dir": "../escaped"},
+        {"url": server.url("/file/absolute.bin"), "dir": tmp.path().join("absolute")}
+    ]);
+    let input = write_input(tmp.path(), &entries.to_string());
+
+    let r = run_dlm_in(&["-i", &input], &output_dir).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("escaped").exists());
+    assert!(!tmp.path().join("absolute").exists());
+    assert_eq!(read(&output_dir.join("escaped/relative.bin")), FILE_BODY);
+    let nested = tmp.path().strip_prefix("/").unwrap();
+    assert_eq!(
+        read(&output_dir.join(nested).join("absolute/absolute.bin")),
+        FILE_BODY
+    );
+}
+
+#[tokio::test]
+async fn input_file_option_headers_stay_with_their_url() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  header=X-Entry: one\n  out=first.txt\n{}\n  out=second.txt\n",
+            server.url("/echo-headers"),
+            server.url("/echo-headers"),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "-m", "1"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(tmp.path().join("first.txt").exists());
+    assert!(tmp.path().join("second.txt").exists());
+    // the second URL, downloaded last, has no header of its own
+    assert!(server.last_echo_headers().get("x-entry").is_none());
+}
+
+#[tokio::test]
+async fn input_file_checksum_option_is_verified() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  checksum=sha256={}\n",
+            server.url("/file/opt-checksum.bin"),
+            "0".repeat(64)
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--retry", "0"], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("opt-checksum.bin").exists());
+}
+
+#[tokio::test]
+async fn input_file_unknown_option_skips_its_url_only() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  colour=blue\n{}\n",
+            server.url("/file/skipped.bin"),
+            server.url("/file/kept.bin"),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("skipped.bin").exists());
+    assert_eq!(read(&tmp.path().join("kept.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn json_array_input_maps_every_field() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let entries = serde_json::json!([
+        {
+            "url": server.url("/echo-headers"),
+            "filename": "late.txt",
+            "headers": {"X-Entry": "late"},
+            "priority": 2
+        },
+        {
+            "url": server.url("/unavailable/mirror.bin"),
+            "mirrors": [server.url("/file/mirror.bin")],
+            "filename": "release.bin",
+            "checksum": format!("sha256={}", file_body_sha256()),
+            "size": FILE_BODY.len()
+        },
+        {
+            "url": server.url("/echo-headers"),
+            "filename": "early.txt",
+            "dir": "nested",
+            "headers": {"X-Entry": "early"},
+            "priority": 1
+        }
+    ]);
+    let input = write_input(tmp.path(), &entries.to_string());
+
+    let r = run_dlm_in(&["-i", &input, "-m", "1", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("nested/early.txt")), b"echoed");
+    assert_eq!(read(&tmp.path().join("late.txt")), b"echoed");
+    assert_eq!(read(&tmp.path().join("release.bin")), FILE_BODY);
+    assert!(!tmp.path().join("mirror.bin").exists());
+    // one download at a time, by priority: "late" came after "early"
+    let headers = server.last_echo_headers();
+    assert_eq!(
+        headers.get("x-entry").and_then(|v| v.to_str().ok()),
+        Some("late")
+    );
+}
+
+#[tokio::test]
+async fn json_lines_input_skips_invalid_entries() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n{{\"url\": \"{}\", \"out\": \"typo.bin\"}}\n{}\n",
+            serde_json::json!({"url": server.url("/file/one.bin")}),
+            server.url("/file/skipped.bin"),
+            serde_json::json!({"url": server.url("/file/two.bin"), "size": FILE_BODY.len() + 1}),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("one.bin")), FILE_BODY);
+    assert!(!tmp.path().join("typo.bin").exists());
+    assert!(!tmp.path().join("skipped.bin").exists());
+    // the announced size does not match the expected one
+    assert!(!tmp.path().join("two.bin").exists());
+}
+
+#[tokio::test]
+async fn json_lines_input_skips_comments() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "# nightly builds\n{}\n\n  # the second one\n{}\n",
+            serde_json::json!({"url": server.url("/file/one.bin"), "priority": 2}),
+            serde_json::json!({"url": server.url("/file/two.bin"), "priority": 1}),
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("one.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("two.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn empty_json_array_is_an_empty_input() {
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(tmp.path(), " []\n");
+
+    let r = run_dlm_in(&["-i", &input], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("the input file is empty"), "{r}");
+}
+
+#[tokio::test]
+async fn from_html_downloads_filtered_links_of_a_page() {
+    let server = TestServer::start().await;
+    let page = server.url("/pages/index.html");
+
+    let (r, tmp) = run_dlm(&["--from-html", &page, "--link-ext", "iso", "--same-host"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("a.iso")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("c.iso")), FILE_BODY);
+    assert!(!tmp.path().join("b.zip").exists());
+    assert!(!tmp.path().join("external.iso").exists());
+}
+
+#[tokio::test]
+async fn from_html_reads_a_local_file() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let page = tmp.path().join("page.html");
+    std::fs::write(
+        &page,
+        format!(
+            r#"<a href="{}">one</a> <a href="{}">two</a> <a href="relative.bin">skipped</a>"#,
+            server.url("/file/one.bin"),
+            server.url("/file/two.bin"),
+        ),
+    )
+    .unwrap();
+
+    let r = run_dlm_in(
+        &["--from-html", page.to_str().unwrap(), "--link-regex", "two"],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("two.bin")), FILE_BODY);
+    assert!(!tmp.path().join("one.bin").exists());
+}
+
+#[tokio::test]
+async fn from_html_without_matching_links_is_an_error() {
+    let server = TestServer::start().await;
+    let page = server.url("/pages/index.html");
+
+    let (r, _tmp) = run_dlm(&["--from-html", &page, "--link-ext", "tar.gz"]).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("no link to download"), "{r}");
+
+    let r = run_dlm_raw(&["--from-html", &page, "--link-regex", "(unclosed"]).await;
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("invalid '--link-regex' value"), "{r}");
+}
+
+#[tokio::test]
+async fn recursive_crawl_recreates_the_listing_tree() {
+    let server = TestServer::start().await;
+    let listing = server.url("/listing/");
+
+    let (r, tmp) = run_dlm(&["--from-html", &listing, "--recursive"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("a.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("sub/b.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("sub/deeper/c.bin")), FILE_BODY);
+    // links outside of the starting directory are not followed
+    assert!(!tmp.path().join("outside.bin").exists());
+}
+
+#[tokio::test]
+async fn recursive_crawl_stops_at_max_depth() {
+    let server = TestServer::start().await;
+    let listing = server.url("/listing/");
+
+    let (r, tmp) = run_dlm(&["--from-html", &listing, "--recursive", "--max-depth", "1"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("a.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("sub/b.bin")), FILE_BODY);
+    assert!(!tmp.path().join("sub/deeper").exists());
+}
+
+#[tokio::test]
+async fn recursive_crawl_is_relative_to_the_starting_directory() {
+    let server = TestServer::start().await;
+    let listing = server.url("/listing/sub/");
+
+    let (r, tmp) = run_dlm(&["--from-html", &listing, "--recursive"]).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    // its parent link is ignored, sub/ is the root of the output dir
+    assert!(!tmp.path().join("a.bin").exists());
+    assert_eq!(read(&tmp.path().join("b.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("deeper/c.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn recursive_requires_a_url() {
+    let tmp = TempDir::new().unwrap();
+    let page = tmp.path().join("page.html");
+    std::fs::write(&page, "<a href=\"https://example.com/a.bin\">a</a>").unwrap();
+
+    let r = run_dlm_in(
+        &["--from-html", page.to_str().unwrap(), "--recursive"],
+        tmp.path(),
+    )
+    .await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("'--recursive' requires a URL"), "{r}");
+}
+
+#[tokio::test]
+async fn output_overrides_the_filename() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/disposition/ignored.bin");
+
+    let r = run_dlm_in(&[&url, "-O", "chosen.bin"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("chosen.bin")), FILE_BODY);
+    assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn output_without_extension_skips_filename_resolution() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/redirect-no-ext");
+
+    let r = run_dlm_in(&[&url, "--output", "plain"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(tmp.path().join("plain").exists());
+}
+
+#[tokio::test]
+async fn output_is_sanitized() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/file/raw.bin");
+
+    let r = run_dlm_in(&[&url, "-O", "../escape:me.bin"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("escape_me.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn output_requires_a_url() {
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(tmp.path(), "http://example.invalid/a.bin\n");
+
+    let r = run_dlm_in(&["-i", &input, "-O", "a.bin"], tmp.path()).await;
+
+    assert_ne!(r.code, 0, "{r}");
+    assert!(r.stderr.contains("'--output' requires a URL"), "{r}");
+}
+
+#[tokio::test]
+async fn resume_restarts_when_server_ignores_range() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // garbage prefix: it must be dropped, not kept in front of the full body
+    std::fs::write(
+        tmp.path().join("ignored.bin.part"),
+        vec![b'x'; FILE_BODY.len() / 2],
+    )
+    .unwrap();
+    let url = server.url("/ignored-range/ignored.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("ignored.bin")), FILE_BODY);
+    assert!(!tmp.path().join("ignored.bin.part").exists());
+}
+
+#[tokio::test]
+async fn resume_rejects_content_range_at_another_offset() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let prefix = &FILE_BODY[..FILE_BODY.len() / 2];
+    std::fs::write(tmp.path().join("shifted.bin.part"), prefix).unwrap();
+    let url = server.url("/shifted-range/shifted.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert!(!tmp.path().join("shifted.bin").exists());
+    // the misplaced body was not appended
+    assert_eq!(read(&tmp.path().join("shifted.bin.part")), prefix);
+}
+
+#[tokio::test]
+async fn resume_restarts_when_head_reports_a_new_version() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // prefix of an older version of the file
+    std::fs::write(
+        tmp.path().join("changed.bin.part"),
+        vec![b'x'; FILE_BODY.len() / 2],
+    )
+    .unwrap();
+    write_sidecar(&tmp.path().join("changed.bin.part"), "\"v1\"");
+    let url = server.url("/versioned/changed.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("changed.bin")), FILE_BODY);
+    assert!(!tmp.path().join("changed.bin.part.dlm.json").exists());
+}
+
+#[tokio::test]
+async fn resume_sends_if_range_and_restarts_on_full_body() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // HEAD claims the stored version is still current, the GET knows better
+    std::fs::write(
+        tmp.path().join("stale.bin.part"),
+        vec![b'x'; FILE_BODY.len() / 2],
+    )
+    .unwrap();
+    write_sidecar(&tmp.path().join("stale.bin.part"), "\"v1\"");
+    let url = server.url("/stale-head/stale.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("stale.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn resume_continues_when_validator_matches() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    std::fs::write(
+        tmp.path().join("same.bin.part"),
+        &FILE_BODY[..FILE_BODY.len() / 2],
+    )
+    .unwrap();
+    write_sidecar(&tmp.path().join("same.bin.part"), CURRENT_ETAG);
+    let url = server.url("/versioned/same.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("same.bin")), FILE_BODY);
+    assert!(!tmp.path().join("same.bin.part.dlm.json").exists());
+}
+
+#[tokio::test]
+async fn interrupted_download_leaves_sidecar() {
+    let server = TestServer::start().await;
+    let url = server.url("/cut-stream/data.bin");
+
+    let (_r, dir) = no_hang(run_dlm(&[&url, "--retry", "0"])).await;
+
+    let sidecar = std::fs::read(dir.path().join("data.bin.part.dlm.json")).unwrap();
+    let sidecar: serde_json::Value = serde_json::from_slice(&sidecar).unwrap();
+    assert_eq!(sidecar["url"], url.as_str());
+    assert_eq!(sidecar["content_length"], FILE_BODY.len());
+    assert!(sidecar["started_at"].is_string());
+}
+
+#[tokio::test]
+async fn resume_restarts_when_length_changed() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // the `.part` was started when the file was twice as large
+    let part = tmp.path().join("resized.bin.part");
+    std::fs::write(&part, vec![b'x'; FILE_BODY.len() / 2]).unwrap();
+    let sidecar = serde_json::json!({
+        "url": "http://old.example/resized.bin",
+        "content_length": FILE_BODY.len() * 2,
+        "etag": null,
+        "last_modified": null,
+        "segments": [],
+        "started_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z",
+    });
+    std::fs::write(
+        tmp.path().join("resized.bin.part.dlm.json"),
+        sidecar.to_string(),
+    )
+    .unwrap();
+    let url = server.url("/file/resized.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("resized.bin")), FILE_BODY);
+    assert!(!tmp.path().join("resized.bin.part.dlm.json").exists());
+}
+
+#[tokio::test]
+async fn update_downloads_missing_file_and_keeps_its_etag() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/versioned/fresh.bin");
+
+    let r = run_dlm_in(&[&url, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("fresh.bin")), FILE_BODY);
+    let sidecar = std::fs::read(tmp.path().join("fresh.bin.dlm.json")).unwrap();
+    let sidecar: serde_json::Value = serde_json::from_slice(&sidecar).unwrap();
+    assert_eq!(sidecar["etag"], CURRENT_ETAG);
+}
+
+#[tokio::test]
+async fn update_skips_file_with_current_etag() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("current.bin");
+    std::fs::write(&path, "local copy").unwrap();
+    write_sidecar(&path, CURRENT_ETAG);
+    let url = server.url("/versioned/current.bin");
+
+    let r = run_dlm_in(&[&url, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), b"local copy");
+}
+
+#[tokio::test]
+async fn update_replaces_file_with_other_etag() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("outdated.bin");
+    std::fs::write(&path, "old version").unwrap();
+    write_sidecar(&path, "\"v1\"");
+    let url = server.url("/versioned/outdated.bin");
+
+    let r = run_dlm_in(&[&url, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), FILE_BODY);
+    assert!(!tmp.path().join("outdated.bin.part").exists());
+}
+
+#[tokio::test]
+async fn update_compares_etag_when_head_ignores_if_none_match() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let current = tmp.path().join("current.bin");
+    std::fs::write(&current, "local copy").unwrap();
+    write_sidecar(&current, CURRENT_ETAG);
+    let outdated = tmp.path().join("outdated.bin");
+    std::fs::write(&outdated, "old version").unwrap();
+    write_sidecar(&outdated, "\"v1\"");
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n{}\n",
+            server.url("/unconditional/current.bin"),
+            server.url("/unconditional/outdated.bin")
+        ),
+    );
+
+    let r = run_dlm_in(&["-i", &input, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&current), b"local copy");
+    assert_eq!(read(&outdated), FILE_BODY);
+}
+
+#[tokio::test]
+async fn update_skips_file_newer_than_remote() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // written now, after the remote `Last-Modified`
+    let path = tmp.path().join("recent.bin");
+    std::fs::write(&path, "local copy").unwrap();
+    let url = server.url("/dated/recent.bin");
+
+    let r = run_dlm_in(&[&url, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), b"local copy");
+}
+
+#[tokio::test]
+async fn update_replaces_file_older_than_remote() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("old.bin");
+    std::fs::write(&path, "old version").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH)
+        .unwrap();
+    let url = server.url("/dated/old.bin");
+
+    let r = run_dlm_in(&[&url, "--update", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), FILE_BODY);
+}
+
+#[tokio::test]
+async fn existing_file_is_kept_without_update() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("kept.bin");
+    std::fs::write(&path, "old version").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH)
+        .unwrap();
+    let url = server.url("/dated/kept.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), b"old version");
+}
+
+#[tokio::test]
+async fn remote_time_sets_mtime_from_last_modified() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/dated/dated.bin");
+
+    let r = run_dlm_in(&[&url, "--remote-time", "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    let path = tmp.path().join("dated.bin");
+    assert_eq!(read(&path), FILE_BODY);
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    assert_eq!(modified, last_modified_time());
+}
+
+#[tokio::test]
+async fn remote_time_then_update_skips_unchanged_file() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/dated/synced.bin");
+    let args = [url.as_str(), "--update", "--remote-time", "--retry", "0"];
+
+    let first = run_dlm_in(&args, tmp.path()).await;
+    assert_eq!(first.code, 0, "{first}");
+    let path = tmp.path().join("synced.bin");
+    std::fs::write(&path, "local copy").unwrap();
+    // writing moved the mtime to now, date it back like the remote file
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(last_modified_time())
+        .unwrap();
+
+    let second = run_dlm_in(&args, tmp.path()).await;
+
+    assert_eq!(second.code, 0, "{second}");
+    assert_eq!(read(&path), b"local copy");
+}
+
+/// `LAST_MODIFIED_DATE` as a `SystemTime`.
+fn last_modified_time() -> std::time::SystemTime {
+    // Wed, 21 Oct 2015 07:28:00 GMT
+    std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_445_412_480)
+}
+
+#[tokio::test]
+async fn on_conflict_overwrite_replaces_existing_file() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("data.bin");
+    std::fs::write(&path, "old version").unwrap();
+    let url = server.url("/file/data.bin");
+
+    let r = run_dlm_in(&[&url, "--on-conflict", "overwrite"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), FILE_BODY);
+}
+
+#[tokio::test]
+async fn on_conflict_rename_numbers_new_file() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    std::fs::write(tmp.path().join("data.bin"), "first").unwrap();
+    std::fs::write(tmp.path().join("data.1.bin"), "second").unwrap();
+    let url = server.url("/file/data.bin");
+
+    let r = run_dlm_in(&[&url, "--on-conflict", "rename"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("data.bin")), b"first");
+    assert_eq!(read(&tmp.path().join("data.1.bin")), b"second");
+    assert_eq!(read(&tmp.path().join("data.2.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn on_conflict_backup_moves_old_file_aside() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let path = tmp.path().join("data.bin");
+    std::fs::write(&path, "old version").unwrap();
+    let url = server.url("/file/data.bin");
+
+    let r = run_dlm_in(&[&url, "--on-conflict", "backup"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&path), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("data.bin.bak")), b"old version");
+}
+
+#[tokio::test]
+async fn on_conflict_skip_conflicts_with_update() {
+    let server = TestServer::start().await;
+    let url = server.url("/file/data.bin");
+
+    let (r, _dir) = run_dlm(&[&url, "--update", "--on-conflict", "skip"]).await;
+
+    assert_eq!(r.code, 1, "{r}");
+    assert!(
+        r.stderr
+            .contains("'--update' cannot be used with '--on-conflict skip'"),
+        "{r}"
+    );
+}
+
+#[tokio::test]
+async fn duplicate_destination_in_batch_fails_the_duplicate() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // two versions of the same file, downloaded concurrently
+    let links = [
+        server.url("/slow/setup.exe"),
+        server.url("/slow/setup.exe?version=2"),
+    ];
+    let input = write_input(tmp.path(), &links.join("\n"));
+
+    let r = no_hang(run_dlm_in(
+        &["-i", &input, "--max-concurrent", "2", "--retry", "0"],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("setup.exe")), FILE_BODY);
+    // the duplicate never wrote into the shared `.part`
+    assert_eq!(server.max_in_flight(), 1);
+    assert!(!tmp.path().join("setup.exe.part").exists());
+}
+
+#[tokio::test]
+async fn duplicate_destination_in_batch_is_renamed() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let links = [
+        server.url("/slow/setup.exe"),
+        server.url("/slow/setup.exe?version=2"),
+    ];
+    let input = write_input(tmp.path(), &links.join("\n"));
+
+    let r = no_hang(run_dlm_in(
+        &[
+            "-i",
+            &input,
+            "--max-concurrent",
+            "2",
+            "--on-conflict",
+            "rename",
+        ],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("setup.exe")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("setup.1.exe")), FILE_BODY);
+    assert_eq!(server.max_in_flight(), 2);
+}
+
+#[tokio::test]
+async fn sequential_duplicate_destination_does_not_replace_the_first() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // the duplicate only starts once the first file is saved
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  out=setup.exe\n{}\n  out=setup.exe\n",
+            server.url("/file/a.bin"),
+            server.url("/echo-headers")
+        ),
+    );
+
+    let r = no_hang(run_dlm_in(
+        &[
+            "-i",
+            &input,
+            "--max-concurrent",
+            "1",
+            "--on-conflict",
+            "overwrite",
+            "--retry",
+            "0",
+        ],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("setup.exe")), FILE_BODY);
+    assert!(!tmp.path().join("setup.1.exe").exists());
+}
+
+#[tokio::test]
+async fn sequential_duplicate_destination_is_renamed() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let input = write_input(
+        tmp.path(),
+        &format!(
+            "{}\n  out=setup.exe\n{}\n  out=setup.exe\n",
+            server.url("/file/a.bin"),
+            server.url("/echo-headers")
+        ),
+    );
+
+    let r = no_hang(run_dlm_in(
+        &[
+            "-i",
+            &input,
+            "--max-concurrent",
+            "1",
+            "--on-conflict",
+            "rename",
+        ],
+        tmp.path(),
+    ))
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("setup.exe")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("setup.1.exe")), b"echoed");
+}
+
+#[tokio::test]
+async fn part_locked_by_another_process_is_left_alone() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let part = tmp.path().join("locked.bin.part");
+    let prefix = &FILE_BODY[..FILE_BODY.len() / 2];
+    std::fs::write(&part, prefix).unwrap();
+    // stand in for another dlm process writing the same `.part`
+    let lock_path = tmp.path().join("locked.bin.part.lock");
+    let mut lock = std::fs::File::create(&lock_path).unwrap();
+    lock.try_lock().unwrap();
+    write!(lock, "4242").unwrap();
+    let url = server.url("/file/locked.bin");
+
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert!(!tmp.path().join("locked.bin").exists(), "{r}");
+    assert_eq!(read(&part), prefix);
+
+    // once released, the download resumes and cleans up the lock file, on
+    // Unix where removed lock files can be told apart
+    drop(lock);
+    let r = run_dlm_in(&[&url, "--retry", "0"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("locked.bin")), FILE_BODY);
+    assert_eq!(lock_path.exists(), !cfg!(unix));
+}
+
+#[tokio::test]
+async fn keep_paths_mirrors_url_structure() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    // same file name under two different paths
+    let links = [
+        server.url("/file/data.bin"),
+        server.url("/no-range/data.bin"),
+    ];
+    let input = write_input(tmp.path(), &links.join("\n"));
+    let out = tmp.path();
+
+    let r = run_dlm_in(&["-i", &input, "--keep-paths"], tmp.path()).await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&out.join("127.0.0.1/file/data.bin")), FILE_BODY);
+    assert_eq!(read(&out.join("127.0.0.1/no-range/data.bin")), FILE_BODY);
+    assert!(!out.join("data.bin").exists());
+}
+
+#[tokio::test]
+async fn output_template_uses_input_line_number() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let content = format!(
+        "{}\n\n# second file\n{}\n",
+        server.url("/file/a.bin"),
+        server.url("/file/b.bin")
+    );
+    let input = write_input(tmp.path(), &content);
+
+    let r = run_dlm_in(
+        &["-i", &input, "--output-template", "{index:03}_{filename}"],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    assert_eq!(read(&tmp.path().join("001_a.bin")), FILE_BODY);
+    assert_eq!(read(&tmp.path().join("004_b.bin")), FILE_BODY);
+}
+
+#[tokio::test]
+async fn output_template_numbers_each_url_of_a_pattern() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let content = format!(
+        "{}\n{}\n",
+        server.url("/file/part[1-3].bin"),
+        server.url("/file/last.bin")
+    );
+    let input = write_input(tmp.path(), &content);
+
+    let r = run_dlm_in(
+        &[
+            "-i",
+            &input,
+            "--glob",
+            "--output-template",
+            "{index}_{filename}",
+        ],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    for name in ["1_part1.bin", "2_part2.bin", "3_part3.bin", "4_last.bin"] {
+        assert_eq!(read(&tmp.path().join(name)), FILE_BODY);
+    }
+}
+
+#[tokio::test]
+async fn output_template_creates_directories_from_metadata() {
+    let server = TestServer::start().await;
+    let tmp = TempDir::new().unwrap();
+    let url = server.url("/dated/report.pdf");
+
+    let r = run_dlm_in(
+        &[&url, "--output-template", "{host}/{yyyy}-{mm}/{name}.{ext}"],
+        tmp.path(),
+    )
+    .await;
+
+    assert_eq!(r.code, 0, "{r}");
+    // dated from `LAST_MODIFIED_DATE`
+    assert_eq!(
+        read(&tmp.path().join("127.0.0.1/2015-10/report.pdf")),
+        FILE_BODY
+    );
+}
+
+#[tokio::test]
+async fn output_template_invalid_placeholder() {
+    let server = TestServer::start().await;
+    let url = server.url("/file/data.bin");
+
+    let (r, _dir) = run_dlm(&[&url, "--output-template", "{size}_{filename}"]).await;
+
+    assert_eq!(r.code, 1, "{r}");
+    assert!(r.stderr.contains("unknown placeholder '{size}'"), "{r}");
+}
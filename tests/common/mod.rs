@@ -10,8 +10,8 @@ use axum::Router;
 use axum::body::Body;
 use axum::extract::{OriginalUri, Path, State};
 use axum::http::header::{
-    ACCEPT_RANGES, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, LOCATION,
-    RANGE,
+    ACCEPT_RANGES, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, ETAG,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE,
 };
 use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
@@ -37,6 +37,15 @@ const fn compute_file_body() -> [u8; 64 * 1024] {
     bytes
 }
 
+/// Lowercase hex SHA-256 of `FILE_BODY`, for checksum verification tests.
+pub fn file_body_sha256() -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(FILE_BODY)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
 #[derive(Clone)]
 struct ServerState {
     /// Remaining 503 responses to send from `/flaky` before it succeeds.
@@ -46,6 +55,11 @@ struct ServerState {
     /// what dlm actually sent. `std::sync::Mutex` is fine here — the lock is
     /// never held across an `.await`.
     last_echo_headers: Arc<Mutex<Option<HeaderMap>>>,
+    /// GETs currently being served by `/slow`, and the highest value seen.
+    in_flight: Arc<AtomicU32>,
+    max_in_flight: Arc<AtomicU32>,
+    /// Requests of any method served by `/counted`.
+    counted_requests: Arc<AtomicU32>,
     /// Origin URL the server is reachable at, e.g. "http://127.0.0.1:12345".
     /// Set once at startup; immutable thereafter, hence `Arc<str>` (no lock).
     origin: Arc<str>,
@@ -63,6 +77,9 @@ impl TestServer {
         let state = ServerState {
             flaky_remaining: Arc::new(AtomicU32::new(0)),
             last_echo_headers: Arc::new(Mutex::new(None)),
+            in_flight: Arc::new(AtomicU32::new(0)),
+            max_in_flight: Arc::new(AtomicU32::new(0)),
+            counted_requests: Arc::new(AtomicU32::new(0)),
             origin: Arc::from(format!("http://{addr}")),
         };
 
@@ -77,6 +94,7 @@ impl TestServer {
             .route("/auth/{name}", get(basic_auth_required))
             .route("/echo-headers", get(echo_headers))
             .route("/flaky", get(flaky))
+            .route("/counted/{name}", get(counted))
             .route("/never-found", get(always_404))
             .route("/short/{name}", any(short_body_with_inflated_cl))
             .route("/disposition-star", get(content_disposition_star))
@@ -85,6 +103,18 @@ impl TestServer {
             .route("/cut-stream/{name}", any(cut_stream_mid_body))
             .route("/stall/{name}", any(stall_before_headers))
             .route("/slow-first-byte/{name}", any(slow_first_byte))
+            .route("/slow/{name}", any(slow_tracked))
+            .route("/sha256sums/{name}", get(bsd_checksum_manifest))
+            .route("/unavailable/{name}", any(always_503))
+            .route("/ignored-range/{name}", any(advertised_range_ignored))
+            .route("/shifted-range/{name}", any(shifted_content_range))
+            .route("/versioned/{name}", any(versioned))
+            .route("/stale-head/{name}", any(stale_head))
+            .route("/unconditional/{name}", any(unconditional))
+            .route("/dated/{name}", any(dated))
+            .route("/pages/index.html", get(index_page))
+            .route("/listing/", get(autoindex))
+            .route("/listing/{*path}", get(autoindex))
             .with_state(state.clone());
 
         tokio::spawn(async move {
@@ -107,7 +137,16 @@ impl TestServer {
             .expect("no request recorded on /echo-headers")
     }
 
+    /// Highest number of GETs `/slow` has served at the same time.
+    pub fn max_in_flight(&self) -> u32 {
+        self.state.max_in_flight.load(Ordering::SeqCst)
+    }
+
     /// Make `/flaky` return 503 the next `n` times, then succeed.
+    pub fn counted_requests(&self) -> u32 {
+        self.state.counted_requests.load(Ordering::SeqCst)
+    }
+
     pub fn set_flaky_fails(&self, n: u32) {
         self.state.flaky_remaining.store(n, Ordering::SeqCst);
     }
@@ -125,6 +164,114 @@ async fn serve_no_range(Path(_name): Path<String>, headers: HeaderMap) -> Respon
     serve_with_range(FILE_BODY, &headers, false)
 }
 
+/// HEAD advertises Range support but GET always answers 200 with the full
+/// body, as some caching proxies do.
+async fn advertised_range_ignored(method: Method, _path: Path<String>) -> Response {
+    if method == Method::HEAD {
+        return head_metadata(FILE_BODY);
+    }
+    Response::new(Body::from(FILE_BODY))
+}
+
+/// HEAD advertises Range support but a ranged GET answers 206 with the body
+/// from its very first byte, whatever the offset asked for.
+async fn shifted_content_range(method: Method, _path: Path<String>) -> Response {
+    if method == Method::HEAD {
+        return head_metadata(FILE_BODY);
+    }
+    let mut resp = Response::new(Body::from(FILE_BODY));
+    *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+    resp.headers_mut().insert(
+        CONTENT_RANGE,
+        HeaderValue::from_str(&format!(
+            "bytes 0-{}/{}",
+            FILE_BODY.len() - 1,
+            FILE_BODY.len()
+        ))
+        .unwrap(),
+    );
+    resp
+}
+
+/// Current version of the resources served by `/versioned`, `/stale-head`
+/// and `/unconditional`.
+pub const CURRENT_ETAG: &str = "\"v2\"";
+
+/// Serves `FILE_BODY` as version `CURRENT_ETAG`. Honors `If-Range`: a range
+/// conditioned on another version gets the whole body with a 200.
+async fn versioned(method: Method, _path: Path<String>, headers: HeaderMap) -> Response {
+    if headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|v| v == CURRENT_ETAG)
+    {
+        return with_etag(StatusCode::NOT_MODIFIED.into_response(), CURRENT_ETAG);
+    }
+    if method == Method::HEAD {
+        return with_etag(head_metadata(FILE_BODY), CURRENT_ETAG);
+    }
+    serve_versioned(&headers)
+}
+
+/// Like `/versioned`, but HEAD still reports the previous version `"v1"`, as
+/// a stale cache would: only `If-Range` on the GET reveals the change.
+async fn stale_head(method: Method, _path: Path<String>, headers: HeaderMap) -> Response {
+    if method == Method::HEAD {
+        return with_etag(head_metadata(FILE_BODY), "\"v1\"");
+    }
+    serve_versioned(&headers)
+}
+
+/// Like `/versioned`, but HEAD ignores `If-None-Match` and always answers
+/// 200 with the current version.
+async fn unconditional(method: Method, _path: Path<String>, headers: HeaderMap) -> Response {
+    if method == Method::HEAD {
+        return with_etag(head_metadata(FILE_BODY), CURRENT_ETAG);
+    }
+    serve_versioned(&headers)
+}
+
+fn serve_versioned(headers: &HeaderMap) -> Response {
+    let if_range = headers.get(IF_RANGE).and_then(|v| v.to_str().ok());
+    let resp = match if_range {
+        Some(validator) if validator != CURRENT_ETAG => {
+            let mut without_range = headers.clone();
+            without_range.remove(RANGE);
+            serve_with_range(FILE_BODY, &without_range, true)
+        }
+        _ => serve_with_range(FILE_BODY, headers, true),
+    };
+    with_etag(resp, CURRENT_ETAG)
+}
+
+/// `Last-Modified` of the resources served by `/dated`.
+pub const LAST_MODIFIED_DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+/// Serves `FILE_BODY` last modified at `LAST_MODIFIED_DATE`. Honors
+/// `If-Modified-Since` with a 304 when the client copy is not older.
+async fn dated(method: Method, _path: Path<String>, headers: HeaderMap) -> Response {
+    let parse = |date: &str| jiff::fmt::rfc2822::parse(date).ok().map(|d| d.timestamp());
+    let since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse);
+    let mut resp = match since {
+        Some(since) if parse(LAST_MODIFIED_DATE).is_some_and(|date| date <= since) => {
+            StatusCode::NOT_MODIFIED.into_response()
+        }
+        _ if method == Method::HEAD => head_metadata(FILE_BODY),
+        _ => serve_with_range(FILE_BODY, &headers, true),
+    };
+    resp.headers_mut()
+        .insert(LAST_MODIFIED, HeaderValue::from_static(LAST_MODIFIED_DATE));
+    resp
+}
+
+fn with_etag(mut resp: Response, etag: &'static str) -> Response {
+    resp.headers_mut()
+        .insert(ETAG, HeaderValue::from_static(etag));
+    resp
+}
+
 /// HEAD returns 405; GET works (with Range support).
 async fn reject_head_get_works(
     method: Method,
@@ -203,10 +350,76 @@ async fn flaky(State(state): State<ServerState>, headers: HeaderMap) -> Response
     serve_with_range(FILE_BODY, &headers, true)
 }
 
+/// Serves `FILE_BODY` like `/file/:name`, counting the requests.
+async fn counted(
+    Path(_name): Path<String>,
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Response {
+    state.counted_requests.fetch_add(1, Ordering::SeqCst);
+    serve_with_range(FILE_BODY, &headers, true)
+}
+
+/// BSD-style manifest listing the checksum of `FILE_BODY` under `name`.
+async fn bsd_checksum_manifest(Path(name): Path<String>) -> String {
+    format!("SHA256 ({name}) = {}\n", file_body_sha256())
+}
+
+/// Index page linking to files of this server with relative, absolute and
+/// duplicate links, and to a file of another host (`localhost` instead of
+/// `127.0.0.1`).
+async fn index_page(State(state): State<ServerState>) -> Response {
+    let other_host = state.origin.replace("127.0.0.1", "localhost");
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html><head><link rel="stylesheet" href="/static/style.css"></head>
+<body>
+  <a href="../file/a.iso">a.iso</a>
+  <a href="/file/b.zip">b.zip</a>
+  <a href='/file/c.iso?mirror=1&amp;fast=1'>c.iso</a>
+  <a href="../file/a.iso#again">a.iso again</a>
+  <a href="{other_host}/file/external.iso">external</a>
+  <a href="mailto:admin@example.com">contact</a>
+</body></html>"#
+    );
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response()
+}
+
+/// nginx-style autoindex of `/listing/`: `a.bin`, `sub/b.bin` and
+/// `sub/deeper/c.bin`, with the parent, sorting and out-of-tree links such
+/// listings also carry. Paths not ending with `/` serve `FILE_BODY`.
+async fn autoindex(OriginalUri(uri): OriginalUri, headers: HeaderMap) -> Response {
+    let entries: &[&str] = match uri.path() {
+        "/listing/" => &["../", "?C=N;O=D", "a.bin", "sub/", "/file/outside.bin"],
+        "/listing/sub/" => &["../", "b.bin", "deeper/"],
+        "/listing/sub/deeper/" => &["../", "c.bin"],
+        path if path.ends_with('/') => return StatusCode::NOT_FOUND.into_response(),
+        _ => return serve_with_range(FILE_BODY, &headers, true),
+    };
+    let links: String = entries
+        .iter()
+        .map(|entry| format!("<a href=\"{entry}\">{entry}</a>\n"))
+        .collect();
+    let html = format!(
+        "<html><head><title>Index of {0}</title></head><body><h1>Index of {0}</h1><hr><pre>{links}</pre><hr></body></html>",
+        uri.path()
+    );
+    ([(axum::http::header::CONTENT_TYPE, "text/html")], html).into_response()
+}
+
 async fn always_404() -> Response {
     StatusCode::NOT_FOUND.into_response()
 }
 
+/// A mirror that is down for good: every request gets a retryable 503.
+async fn always_503() -> Response {
+    StatusCode::SERVICE_UNAVAILABLE.into_response()
+}
+
 /// HEAD claims `Content-Length: FILE_BODY.len()`; GET serves only half. dlm
 /// should detect the mismatch and raise `IncompleteDownload`.
 async fn short_body_with_inflated_cl(method: Method, _path: Path<String>) -> Response {
@@ -295,6 +508,25 @@ async fn slow_first_byte(method: Method, _path: Path<String>) -> Response {
     resp
 }
 
+/// HEAD answers immediately; GET holds the request for 300ms before serving
+/// the body while tracking how many GETs are in flight. Lets tests assert on
+/// the concurrency dlm actually used.
+async fn slow_tracked(
+    method: Method,
+    Path(_name): Path<String>,
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Response {
+    if method == Method::HEAD {
+        return head_metadata(FILE_BODY);
+    }
+    let now = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    state.max_in_flight.fetch_max(now, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    serve_with_range(FILE_BODY, &headers, true)
+}
+
 // ---------- helpers ----------
 
 fn redirect(location: &str) -> Response {